@@ -0,0 +1,273 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+//! Decode captured bytes with a schema, or encode JSON back into bytes.
+//!
+//! The schema file is either the JSON from `Schema::to_json` or an ssmarshal-encoded `Schema`.
+//! Input comes from a file or standard input, and is raw bytes unless `--hex` is given.
+
+extern crate ssmarshal;
+
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::{env, fs, process, slice};
+
+use ssmarshal::dynamic::{self, Value};
+use ssmarshal::schema::Schema;
+use ssmarshal::{Deserializer, Error, ErrorKind, Limits};
+
+const USAGE: &str = "\
+usage: ssmarshal decode [--hex] [--tree] <schema> [input]
+       ssmarshal encode [--hex] <schema> [input]
+
+decode reads bytes and prints the value as JSON, or with --tree as an indented tree with the
+byte range of each part. encode reads a value as JSON and writes its bytes. The input is read
+from standard input if it's missing or `-`, and --hex reads or writes the bytes as hex, optionally
+prefixed with 0x and separated by spaces or commas.";
+
+/// How deeply decoded values and schemas can be nested, so a recursive schema can't run the
+/// stack out on a crafted capture.
+const MAX_DEPTH: usize = 128;
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let command = args.next();
+    let mut hex = false;
+    let mut tree = false;
+    let mut paths = Vec::new();
+    for arg in args {
+        match &arg[..] {
+            "--hex" => hex = true,
+            "--tree" => tree = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with("--") => usage(),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() || paths.len() > 2 {
+        usage();
+    }
+    let input = paths.get(1).map_or("-", |p| &p[..]);
+
+    let res = match command.as_deref() {
+        Some("decode") => decode(&paths[0], input, hex, tree),
+        Some("encode") if !tree => encode(&paths[0], input, hex),
+        _ => usage(),
+    };
+    if let Err(msg) = res {
+        eprintln!("error: {}", msg);
+        process::exit(1);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+/// How to refer to an input in errors.
+fn name(path: &str) -> &str {
+    if path == "-" {
+        "<stdin>"
+    } else {
+        path
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>, String> {
+    let mut buf = Vec::new();
+    let res = if path == "-" {
+        io::stdin().read_to_end(&mut buf).map(|_| ())
+    } else {
+        fs::File::open(path).and_then(|mut f| f.read_to_end(&mut buf).map(|_| ()))
+    };
+    res.map_err(|e| format!("{}: {}", name(path), e))?;
+    Ok(buf)
+}
+
+fn read_text(path: &str) -> Result<String, String> {
+    String::from_utf8(read(path)?).map_err(|_| format!("{}: not valid UTF-8", name(path)))
+}
+
+fn load_schema(path: &str) -> Result<Schema, String> {
+    let buf = read(path)?;
+    let json = buf.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{');
+    let res = if json {
+        let text =
+            std::str::from_utf8(&buf).map_err(|_| format!("{}: not valid UTF-8", name(path)))?;
+        Schema::from_json(text)
+    } else {
+        Deserializer::new(&buf)
+            .with_limits(Limits::new().with_max_depth(MAX_DEPTH))
+            .deserialize::<Schema>()
+    };
+    res.map_err(|e| format!("{}: {}", name(path), e))
+}
+
+fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    for token in text.split(|c: char| c.is_ascii_whitespace() || c == ',' || c == ':') {
+        let digits = token
+            .strip_prefix("0x")
+            .or_else(|| token.strip_prefix("0X"));
+        let digits = digits.unwrap_or(token).as_bytes();
+        if digits.len() % 2 != 0 {
+            return Err(format!("odd number of hex digits in `{}`", token));
+        }
+        for pair in digits.chunks(2) {
+            let byte = std::str::from_utf8(pair)
+                .ok()
+                .and_then(|s| u8::from_str_radix(s, 16).ok())
+                .ok_or_else(|| format!("invalid hex `{}`", String::from_utf8_lossy(pair)))?;
+            bytes.push(byte);
+        }
+    }
+    Ok(bytes)
+}
+
+fn decode(schema: &str, input: &str, hex: bool, tree: bool) -> Result<(), String> {
+    let schema = load_schema(schema)?;
+    let buf = if hex {
+        parse_hex(&read_text(input)?)?
+    } else {
+        read(input)?
+    };
+    let limits = Limits::new().with_max_depth(MAX_DEPTH);
+    let (val, offsets) =
+        dynamic::decode_with_offsets_limited(&schema, &buf, limits).map_err(|e| e.to_string())?;
+    // the capture isn't what the schema describes, so print nothing of it
+    let len = offsets.first().map_or(0, |r| r.end);
+    if len < buf.len() {
+        let err = Error::new(ErrorKind::TrailingBytes);
+        return Err(format!("{} at byte {}", err, len));
+    }
+    if tree {
+        let mut printer = Tree {
+            offsets: offsets.iter(),
+            out: String::new(),
+        };
+        printer.node(0, "", &val);
+        print!("{}", printer.out);
+    } else {
+        println!("{}", val.to_json(true));
+    }
+    Ok(())
+}
+
+fn encode(schema: &str, input: &str, hex: bool) -> Result<(), String> {
+    let schema = load_schema(schema)?;
+    let text = read_text(input)?;
+    let val = Value::from_json(&text).map_err(|e| format!("{}: {}", name(input), e))?;
+    let buf = dynamic::to_vec(&schema, &val).map_err(|e| e.to_string())?;
+    let mut stdout = io::stdout();
+    let res = if hex {
+        let mut text = String::new();
+        for line in buf.chunks(16) {
+            let bytes: Vec<String> = line.iter().map(|b| format!("{:02x}", b)).collect();
+            text.push_str(&bytes.join(" "));
+            text.push('\n');
+        }
+        stdout.write_all(text.as_bytes())
+    } else {
+        stdout.write_all(&buf)
+    };
+    res.and_then(|_| stdout.flush()).map_err(|e| e.to_string())
+}
+
+/// Prints a value one part per line, next to the bytes it was decoded from.
+struct Tree<'a> {
+    /// The ranges from `decode_with_offsets`, in the same order as the walk.
+    offsets: slice::Iter<'a, Range<usize>>,
+    out: String,
+}
+
+impl<'a> Tree<'a> {
+    fn range(&mut self) -> Range<usize> {
+        self.offsets
+            .next()
+            .cloned()
+            .expect("a range for every value")
+    }
+
+    fn line(&mut self, range: Range<usize>, depth: usize, label: &str, summary: &str) {
+        let span = format!("{}..{}", range.start, range.end);
+        let _ = writeln!(
+            self.out,
+            "{:<12}{:indent$}{}{}",
+            span,
+            "",
+            label,
+            summary,
+            indent = depth * 2
+        );
+    }
+
+    fn node(&mut self, depth: usize, label: &str, val: &Value) {
+        let range = self.range();
+        match val {
+            Value::Option(None) => self.line(range, depth, label, "None"),
+            Value::Option(Some(inner)) => {
+                self.line(range, depth, label, "Some");
+                self.node(depth + 1, "", inner);
+            }
+            Value::Seq(elements) => {
+                let summary = format!("[{}]", elements.len());
+                self.line(range, depth, label, &summary);
+                self.elements(depth + 1, elements);
+            }
+            Value::Map(entries) => {
+                let summary = format!("{{{}}}", entries.len());
+                self.line(range, depth, label, &summary);
+                for (key, val) in entries {
+                    self.node(depth + 1, "key: ", key);
+                    self.node(depth + 1, "value: ", val);
+                }
+            }
+            Value::Struct(fields) => {
+                self.line(range, depth, label, "{}");
+                self.fields(depth + 1, fields);
+            }
+            Value::Variant(name, contents) => {
+                self.line(range, depth, label, name);
+                // the contents of unit, tuple and struct variants go straight under the name
+                match &**contents {
+                    Value::Unit => {
+                        self.range();
+                    }
+                    Value::Seq(elements) => {
+                        self.range();
+                        self.elements(depth + 1, elements);
+                    }
+                    Value::Struct(fields) => {
+                        self.range();
+                        self.fields(depth + 1, fields);
+                    }
+                    contents => self.node(depth + 1, "", contents),
+                }
+            }
+            _ => {
+                let summary = val.to_json(false);
+                self.line(range, depth, label, &summary);
+            }
+        }
+    }
+
+    fn elements(&mut self, depth: usize, elements: &[Value]) {
+        for (i, val) in elements.iter().enumerate() {
+            self.node(depth, &format!("{}: ", i), val);
+        }
+    }
+
+    fn fields(&mut self, depth: usize, fields: &[(String, Value)]) {
+        for (name, val) in fields {
+            self.node(depth, &format!("{}: ", name), val);
+        }
+    }
+}
@@ -0,0 +1,309 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+//! Encode bounded-capacity collections with a compact length prefix.
+//!
+//! A plain serde sequence or string is prefixed with a length of the configured `Options::Len`
+//! width, which is 8 bytes by default. The collections from `heapless` and `arrayvec` have a
+//! capacity `N` known at compile time, so the modules here prefix them with the narrowest integer
+//! able to hold `N` instead: a `u8` when `N < 256`, a `u16` when `N < 65536` and a `u32` beyond
+//! that. Select them per field:
+//!
+//! ```rust
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate heapless;
+//! # extern crate ssmarshal;
+//! #[derive(Serialize, Deserialize)]
+//! struct Packet {
+//!     #[serde(with = "ssmarshal::bounded::heapless_vec")]
+//!     payload: heapless::Vec<u8, 64>,
+//!     #[serde(with = "ssmarshal::bounded::heapless_string")]
+//!     name: heapless::String<16>,
+//! }
+//!
+//! # fn main() {
+//! let packet = Packet {
+//!     payload: heapless::Vec::from_slice(&[1, 2, 3]).unwrap(),
+//!     name: "hi".parse().unwrap(),
+//! };
+//! let mut buf = [0u8; 16];
+//! assert_eq!(ssmarshal::serialize(&mut buf, &packet).unwrap(), 7);
+//! assert_eq!(buf[..7], [3, 1, 2, 3, 2, b'h', b'i']);
+//! # }
+//! ```
+//!
+//! The prefix takes at most as many bytes as the length field inside the collection, so encoded
+//! values still fit in `size_of::<T>()` bytes. When decoding, a prefix larger than `N` fails with
+//! `ErrorKind::CapacityExceeded` before any element is read. Other serde formats see a tuple of the
+//! length followed by the elements.
+//!
+//! `MaxSize` for the collections themselves counts the full `Options::Len` prefix their own serde
+//! impls write, since the derive can't see `with` attributes. It is correct for fields with or
+//! without the modules here, but overcounts the former by up to 7 bytes each.
+
+use core::convert::TryFrom;
+use core::fmt;
+use core::marker::PhantomData;
+
+use serde::de::{Deserialize, Deserializer, Error, SeqAccess, Unexpected, Visitor};
+use serde::ser::{Error as SerError, Serialize, SerializeTupleStruct, Serializer};
+
+use config::{prefix_width, DefaultOptions, Options, Width};
+
+#[doc(hidden)]
+pub const BOUNDED_TOKEN: &str = "$ssmarshal::private::bounded";
+
+/// A collection which can be rebuilt one element at a time, up to a fixed capacity.
+trait Bounded: Sized {
+    type Item;
+    const CAPACITY: usize;
+
+    fn empty() -> Self;
+
+    /// Append `item`; the caller has already checked there is room for it.
+    fn append(&mut self, item: Self::Item);
+}
+
+fn serialize_prefixed<T: Serialize, S: Serializer>(
+    items: &[T],
+    cap: usize,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let len = items.len();
+    let mut tuple = serializer.serialize_tuple_struct(BOUNDED_TOKEN, len + 1)?;
+    match prefix_width(cap) {
+        1 => tuple.serialize_field(&(len as u8))?,
+        2 => tuple.serialize_field(&(len as u16))?,
+        _ => match u32::try_from(len) {
+            Ok(len) => tuple.serialize_field(&len)?,
+            Err(_) => return Err(S::Error::custom("collection too long for a u32 prefix")),
+        },
+    }
+    for item in items {
+        tuple.serialize_field(item)?;
+    }
+    tuple.end()
+}
+
+fn deserialize_prefixed<'de, C, D>(deserializer: D) -> Result<C, D::Error>
+where
+    C: Bounded,
+    C::Item: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_tuple_struct(
+        BOUNDED_TOKEN,
+        C::CAPACITY.saturating_add(1),
+        PrefixedVisitor(PhantomData),
+    )
+}
+
+struct PrefixedVisitor<C>(PhantomData<C>);
+
+impl<'de, C> Visitor<'de> for PrefixedVisitor<C>
+where
+    C: Bounded,
+    C::Item: Deserialize<'de>,
+{
+    type Value = C;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a length-prefixed sequence of at most {} elements",
+            C::CAPACITY
+        )
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<C, A::Error> {
+        let len = match prefix_width(C::CAPACITY) {
+            1 => seq.next_element::<u8>()?.map(usize::from),
+            2 => seq.next_element::<u16>()?.map(usize::from),
+            _ => seq.next_element::<u32>()?.map(|len| len as usize),
+        };
+        let len = len.ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        if len > C::CAPACITY {
+            return Err(A::Error::invalid_length(len, &self));
+        }
+        let mut out = C::empty();
+        for i in 0..len {
+            match seq.next_element()? {
+                Some(item) => out.append(item),
+                None => return Err(A::Error::invalid_length(i + 1, &self)),
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn invalid_utf8<E: Error>() -> E {
+    E::invalid_value(Unexpected::Other("invalid UTF-8"), &"a UTF-8 string")
+}
+
+/// The length prefix written by the collections' own serde impls, with the default options.
+const LEN_SIZE: usize = <<DefaultOptions as Options>::Len as Width>::BYTES;
+
+macro_rules! max_size_prefixed {
+    ($($ty:ty),*) => {
+        $(
+            /// The size with or without the matching `bounded` module.
+            impl<T: ::MaxSize, const N: usize> ::MaxSize for $ty {
+                const MAX_SIZE: usize = LEN_SIZE + N * T::MAX_SIZE;
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "heapless")]
+max_size_prefixed!(::heapless::Vec<T, N>);
+
+#[cfg(feature = "arrayvec")]
+max_size_prefixed!(::arrayvec::ArrayVec<T, N>);
+
+#[cfg(feature = "heapless")]
+impl<const N: usize> ::MaxSize for ::heapless::String<N> {
+    const MAX_SIZE: usize = LEN_SIZE + N;
+}
+
+#[cfg(feature = "arrayvec")]
+impl<const N: usize> ::MaxSize for ::arrayvec::ArrayString<N> {
+    const MAX_SIZE: usize = LEN_SIZE + N;
+}
+
+#[cfg(feature = "heapless")]
+impl<T, const N: usize> Bounded for ::heapless::Vec<T, N> {
+    type Item = T;
+    const CAPACITY: usize = N;
+
+    fn empty() -> Self {
+        ::heapless::Vec::new()
+    }
+
+    fn append(&mut self, item: T) {
+        let _ = self.push(item);
+    }
+}
+
+#[cfg(feature = "arrayvec")]
+impl<T, const N: usize> Bounded for ::arrayvec::ArrayVec<T, N> {
+    type Item = T;
+    const CAPACITY: usize = N;
+
+    fn empty() -> Self {
+        ::arrayvec::ArrayVec::new()
+    }
+
+    fn append(&mut self, item: T) {
+        let _ = self.try_push(item);
+    }
+}
+
+/// `#[serde(with = "ssmarshal::bounded::heapless_vec")]` for `heapless::Vec<T, N>`.
+#[cfg(feature = "heapless")]
+pub mod heapless_vec {
+    use heapless::Vec;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// Serialize the vector with a length prefix sized for `N`.
+    pub fn serialize<T, S, const N: usize>(
+        val: &Vec<T, N>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        super::serialize_prefixed(val, N, serializer)
+    }
+
+    /// Deserialize a vector encoded by `serialize`.
+    pub fn deserialize<'de, T, D, const N: usize>(deserializer: D) -> Result<Vec<T, N>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        super::deserialize_prefixed(deserializer)
+    }
+}
+
+/// `#[serde(with = "ssmarshal::bounded::heapless_string")]` for `heapless::String<N>`.
+#[cfg(feature = "heapless")]
+pub mod heapless_string {
+    use heapless::{String, Vec};
+    use serde::{Deserializer, Serializer};
+
+    /// Serialize the string's UTF-8 bytes with a length prefix sized for `N`.
+    pub fn serialize<S: Serializer, const N: usize>(
+        val: &String<N>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        super::serialize_prefixed(val.as_bytes(), N, serializer)
+    }
+
+    /// Deserialize a string encoded by `serialize`, checking that it is valid UTF-8.
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<String<N>, D::Error> {
+        let bytes: Vec<u8, N> = super::deserialize_prefixed(deserializer)?;
+        String::from_utf8(bytes).map_err(|_| super::invalid_utf8())
+    }
+}
+
+/// `#[serde(with = "ssmarshal::bounded::array_vec")]` for `arrayvec::ArrayVec<T, N>`.
+#[cfg(feature = "arrayvec")]
+pub mod array_vec {
+    use arrayvec::ArrayVec;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// Serialize the vector with a length prefix sized for `N`.
+    pub fn serialize<T, S, const N: usize>(
+        val: &ArrayVec<T, N>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        super::serialize_prefixed(val, N, serializer)
+    }
+
+    /// Deserialize a vector encoded by `serialize`.
+    pub fn deserialize<'de, T, D, const N: usize>(
+        deserializer: D,
+    ) -> Result<ArrayVec<T, N>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        super::deserialize_prefixed(deserializer)
+    }
+}
+
+/// `#[serde(with = "ssmarshal::bounded::array_string")]` for `arrayvec::ArrayString<N>`.
+#[cfg(feature = "arrayvec")]
+pub mod array_string {
+    use arrayvec::{ArrayString, ArrayVec};
+    use serde::{Deserializer, Serializer};
+
+    /// Serialize the string's UTF-8 bytes with a length prefix sized for `N`.
+    pub fn serialize<S: Serializer, const N: usize>(
+        val: &ArrayString<N>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        super::serialize_prefixed(val.as_bytes(), N, serializer)
+    }
+
+    /// Deserialize a string encoded by `serialize`, checking that it is valid UTF-8.
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<ArrayString<N>, D::Error> {
+        let bytes: ArrayVec<u8, N> = super::deserialize_prefixed(deserializer)?;
+        match ::core::str::from_utf8(&bytes) {
+            Ok(s) => Ok(ArrayString::from(s).expect("length already checked")),
+            Err(_) => Err(super::invalid_utf8()),
+        }
+    }
+}
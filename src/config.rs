@@ -0,0 +1,309 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+//! Encoding options.
+//!
+//! The wire format is chosen at compile time through the type parameters of `Config`, so a
+//! non-default configuration costs nothing at runtime. The default configuration is the format
+//! described in the README: little-endian integers, 8-byte `usize`s and sequence lengths, and
+//! 1-byte enum discriminants, decoded leniently, without describing their types.
+//!
+//! ```rust
+//! use ssmarshal::config::{BigEndian, Config, U16, U32};
+//!
+//! let opts = Config::new()
+//!     .with_endian::<BigEndian>()
+//!     .with_usize::<U32>()
+//!     .with_len::<U16>();
+//!
+//! let mut buf = [0u8; 8];
+//! let len = ssmarshal::serialize_with(&mut buf, &[0x1234u16; 2], opts).unwrap();
+//! assert_eq!(&buf[..len], &[0x12, 0x34, 0x12, 0x34]);
+//! ```
+
+use core::marker::PhantomData;
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// The byte order integers and floats are encoded in.
+pub trait ByteOrder: private::Sealed + Copy + Default {
+    #[doc(hidden)]
+    const BIG_ENDIAN: bool;
+}
+
+/// Least significant byte first. This is the default.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct LittleEndian;
+
+/// Most significant byte first.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct BigEndian;
+
+/// The byte order of the target being compiled for.
+#[cfg(target_endian = "little")]
+pub type NativeEndian = LittleEndian;
+
+/// The byte order of the target being compiled for.
+#[cfg(target_endian = "big")]
+pub type NativeEndian = BigEndian;
+
+impl private::Sealed for LittleEndian {}
+impl private::Sealed for BigEndian {}
+
+impl ByteOrder for LittleEndian {
+    const BIG_ENDIAN: bool = false;
+}
+
+impl ByteOrder for BigEndian {
+    const BIG_ENDIAN: bool = true;
+}
+
+/// The width of an integer on the wire.
+pub trait Width: private::Sealed + Copy + Default {
+    #[doc(hidden)]
+    const BYTES: usize;
+}
+
+/// Widths usable for `usize` and `isize`.
+pub trait UsizeWidth: Width {}
+
+/// Widths usable for sequence length prefixes.
+pub trait LenWidth: Width {}
+
+/// Widths usable for enum discriminants.
+pub trait TagWidth: Width {}
+
+/// One byte.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct U8;
+
+/// Two bytes.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct U16;
+
+/// Four bytes.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct U32;
+
+/// Eight bytes.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct U64;
+
+macro_rules! width {
+    ($($name:ident => $bytes:expr, [$($kind:ident),*]);* $(;)*) => {
+        $(
+            impl private::Sealed for $name {}
+
+            impl Width for $name {
+                const BYTES: usize = $bytes;
+            }
+
+            $(impl $kind for $name {})*
+        )*
+    }
+}
+
+width! {
+    U8 => 1, [LenWidth, TagWidth];
+    U16 => 2, [LenWidth, TagWidth];
+    U32 => 4, [UsizeWidth, LenWidth, TagWidth];
+    U64 => 8, [UsizeWidth, LenWidth];
+}
+
+/// The number of bytes in the length prefix of a bounded collection with capacity `cap`: the
+/// narrowest of a `u8`, `u16` or `u32` able to hold it.
+#[cfg(any(feature = "heapless", feature = "arrayvec", feature = "schema"))]
+pub(crate) const fn prefix_width(cap: usize) -> usize {
+    if cap <= u8::MAX as usize {
+        1
+    } else if cap <= u16::MAX as usize {
+        2
+    } else {
+        4
+    }
+}
+
+/// How strictly buffers are checked when decoding.
+pub trait Mode: private::Sealed + Copy + Default {
+    #[doc(hidden)]
+    const STRICT: bool;
+    #[doc(hidden)]
+    const CANONICAL: bool;
+}
+
+/// Accept any buffer which decodes to a value. This is the default.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Lenient;
+
+/// Fail with `ErrorKind::TrailingBytes` if `deserialize_with` leaves part of the buffer unread,
+/// and with `ErrorKind::MoreElements` if a sequence or map isn't read to the end.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Strict;
+
+/// Like `Strict`, but floats must also use the canonical quiet NaN, failing with
+/// `ErrorKind::NonCanonicalNan` otherwise. Serializing writes every NaN that way, so each value
+/// has exactly one accepted encoding. Maps could list their entries in any order, so both
+/// serializing and deserializing them fail with `ErrorKind::NonCanonicalMap`. So do structs in
+/// the `SelfDescribing` format, which are written as maps from field names to values.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Canonical;
+
+/// The bits of the NaNs `Canonical` accepts: quiet, positive and without payload.
+pub(crate) const CANONICAL_NAN_F32: u32 = 0x7FC0_0000;
+pub(crate) const CANONICAL_NAN_F64: u64 = 0x7FF8_0000_0000_0000;
+
+impl private::Sealed for Lenient {}
+impl private::Sealed for Strict {}
+impl private::Sealed for Canonical {}
+
+impl Mode for Lenient {
+    const STRICT: bool = false;
+    const CANONICAL: bool = false;
+}
+
+impl Mode for Strict {
+    const STRICT: bool = true;
+    const CANONICAL: bool = false;
+}
+
+impl Mode for Canonical {
+    const STRICT: bool = true;
+    const CANONICAL: bool = true;
+}
+
+/// Whether values carry a description of their type.
+pub trait Format: private::Sealed + Copy + Default {
+    #[doc(hidden)]
+    const DESCRIBED: bool;
+}
+
+/// Just the values, as described in the README. This is the default.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Bare;
+
+/// Each value is preceded by a one-byte marker saying what type it is, and structs and enums
+/// carry their field and variant names.
+///
+/// This makes the encoding several times larger, but lets it be decoded without knowing the
+/// type, so `Deserializer` supports `deserialize_any`. That's what `#[serde(flatten)]`,
+/// internally tagged and untagged enums, and transcoding into other formats need. It's meant for
+/// debug captures and host tooling rather than production IPC. The size invariant doesn't hold.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct SelfDescribing;
+
+impl private::Sealed for Bare {}
+impl private::Sealed for SelfDescribing {}
+
+impl Format for Bare {
+    const DESCRIBED: bool = false;
+}
+
+impl Format for SelfDescribing {
+    const DESCRIBED: bool = true;
+}
+
+/// A set of encoding options, accepted by `serialize_with` and `deserialize_with`.
+///
+/// This is implemented by `Config`; there is no reason to implement it yourself.
+pub trait Options: Copy {
+    /// Byte order of integers and floats.
+    type Endian: ByteOrder;
+    /// Width of `usize` and `isize` fields marked with `#[serde(with = "ssmarshal::word")]`.
+    type Usize: UsizeWidth;
+    /// Width of the length prefix of sequences.
+    type Len: LenWidth;
+    /// Width of enum discriminants.
+    type Tag: TagWidth;
+    /// How strictly to decode.
+    type Mode: Mode;
+    /// Whether values describe their own types.
+    type Format: Format;
+}
+
+/// The encoding options, built up from `Config::new()`.
+///
+/// Each `with_*` method returns a configuration differing only in that option.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Config<E = LittleEndian, S = U64, L = U64, T = U8, M = Lenient, F = Bare> {
+    _marker: PhantomData<(E, S, L, T, M, F)>,
+}
+
+/// The options used by `serialize` and `deserialize`.
+pub type DefaultOptions = Config;
+
+impl Config {
+    /// The default options.
+    pub fn new() -> Config {
+        Config {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<E, S, L, T, M, F> Config<E, S, L, T, M, F> {
+    /// Encode integers and floats in byte order `B`.
+    pub fn with_endian<B: ByteOrder>(self) -> Config<B, S, L, T, M, F> {
+        Config {
+            _marker: PhantomData,
+        }
+    }
+
+    /// Encode `usize` and `isize` words as `W`, either `U32` or `U64`. Values which don't fit are
+    /// rejected with `ErrorKind::Overflow`.
+    pub fn with_usize<W: UsizeWidth>(self) -> Config<E, W, L, T, M, F> {
+        Config {
+            _marker: PhantomData,
+        }
+    }
+
+    /// Encode sequence lengths as `W`. Longer sequences are rejected with `ErrorKind::Overflow`.
+    pub fn with_len<W: LenWidth>(self) -> Config<E, S, W, T, M, F> {
+        Config {
+            _marker: PhantomData,
+        }
+    }
+
+    /// Encode enum discriminants as `W`, one of `U8`, `U16` or `U32`. Enums with more variants
+    /// than fit are rejected with `ErrorKind::TooManyVariants`.
+    pub fn with_tag<W: TagWidth>(self) -> Config<E, S, L, W, M, F> {
+        Config {
+            _marker: PhantomData,
+        }
+    }
+
+    /// Decode in mode `N`: `Lenient`, `Strict` or `Canonical`.
+    pub fn with_mode<N: Mode>(self) -> Config<E, S, L, T, N, F> {
+        Config {
+            _marker: PhantomData,
+        }
+    }
+
+    /// Encode in format `G`: `Bare` or `SelfDescribing`.
+    pub fn with_format<G: Format>(self) -> Config<E, S, L, T, M, G> {
+        Config {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<E, S, L, T, M, F> Options for Config<E, S, L, T, M, F>
+where
+    E: ByteOrder,
+    S: UsizeWidth,
+    L: LenWidth,
+    T: TagWidth,
+    M: Mode,
+    F: Format,
+{
+    type Endian = E;
+    type Usize = S;
+    type Len = L;
+    type Tag = T;
+    type Mode = M;
+    type Format = F;
+}
@@ -0,0 +1,991 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+use serde::de::{DeserializeSeed, IgnoredAny, IntoDeserializer, Visitor};
+use serde::Deserialize;
+
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use encode_unicode::Utf8Char;
+
+#[cfg(any(feature = "heapless", feature = "arrayvec"))]
+use bounded;
+use config::{
+    ByteOrder, DefaultOptions, Format, Mode, Options, Width, CANONICAL_NAN_F32, CANONICAL_NAN_F64,
+};
+use describe::Marker;
+use error::Crumb;
+use input::{Input, SliceInput};
+use limits::{Limit, Limits};
+use {ns, word, Error, ErrorKind};
+
+/// A cursor which deserializes values from an `Input`, usually a byte buffer.
+///
+/// Each value is read directly after the previous one, mirroring `Serializer`. The free function
+/// `deserialize` is a shorthand for deserializing a single value from the start of a buffer.
+pub struct Deserializer<R, O = DefaultOptions> {
+    input: R,
+    opts: O,
+    limits: Limits,
+    // how deeply nested the current value is, and how many elements it has had so far
+    depth: usize,
+    elements: usize,
+}
+
+impl<'a> Deserializer<SliceInput<'a>> {
+    /// Create a deserializer which reads from the start of `buf` using the default options.
+    pub fn new(buf: &'a [u8]) -> Deserializer<SliceInput<'a>> {
+        Deserializer::with_options(buf, DefaultOptions::new())
+    }
+}
+
+impl<'a, O: Options> Deserializer<SliceInput<'a>, O> {
+    /// Create a deserializer which reads from the start of `buf` using the given options.
+    pub fn with_options(buf: &'a [u8], opts: O) -> Deserializer<SliceInput<'a>, O> {
+        Deserializer::from_input(SliceInput::new(buf), opts)
+    }
+
+    /// The number of bytes left to read.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.input.remaining()
+    }
+
+    /// Check that the whole buffer has been read, failing with `ErrorKind::TrailingBytes` if not.
+    pub fn end(&self) -> Result<(), Error> {
+        if self.remaining() > 0 {
+            return Err(Error::new(ErrorKind::TrailingBytes).at(self.position()));
+        }
+        Ok(())
+    }
+
+    /// Get back the underlying buffer.
+    pub fn into_inner(self) -> &'a [u8] {
+        self.input.into_inner()
+    }
+}
+
+impl<'de, R: Input<'de>, O: Options> Deserializer<R, O> {
+    /// Create a deserializer which reads from `input` using the given options.
+    pub fn from_input(input: R, opts: O) -> Deserializer<R, O> {
+        Deserializer {
+            input,
+            opts,
+            limits: Limits::new(),
+            depth: 0,
+            elements: 0,
+        }
+    }
+
+    /// Check each value deserialized from now on against `limits`.
+    pub fn with_limits(mut self, limits: Limits) -> Deserializer<R, O> {
+        self.limits = limits;
+        self
+    }
+
+    /// Deserialize the next value from the input.
+    pub fn deserialize<T: Deserialize<'de>>(&mut self) -> DeserializeResult<T> {
+        self.elements = 0;
+        T::deserialize(&mut *self).map_err(|e| e.at(self.position()))
+    }
+
+    /// Deserialize the next value from the input into `place`, overwriting it.
+    ///
+    /// Types whose `Deserialize` implements `deserialize_in_place` (arrays, tuples, and derived
+    /// impls with serde_derive's `deserialize_in_place` feature) are decoded straight into
+    /// `place` rather than built up on the stack and moved. If this fails, `place` may be left
+    /// partly overwritten.
+    pub fn deserialize_in_place<T: Deserialize<'de>>(
+        &mut self,
+        place: &mut T,
+    ) -> DeserializeResult<()> {
+        self.elements = 0;
+        T::deserialize_in_place(&mut *self, place).map_err(|e| e.at(self.position()))
+    }
+
+    /// Deserialize the next value from the input using a `DeserializeSeed`, for values which
+    /// need some runtime context to be decoded.
+    pub fn deserialize_seed<S: DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> DeserializeResult<S::Value> {
+        self.elements = 0;
+        seed.deserialize(&mut *self)
+            .map_err(|e| e.at(self.position()))
+    }
+
+    /// The number of bytes read so far.
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.input.position()
+    }
+
+    /// The options this deserializer decodes with.
+    pub fn options(&self) -> O {
+        self.opts
+    }
+
+    /// The limits values are checked against.
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Get back the input.
+    pub fn into_input(self) -> R {
+        self.input
+    }
+
+    #[inline]
+    fn read_into(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        let start = self.position();
+        self.input.read_exact(buf).map_err(|e| e.at(start))
+    }
+
+    #[inline]
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut arr = [0; N];
+        self.read_into(&mut arr)?;
+        Ok(arr)
+    }
+
+    #[inline]
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        let [val] = self.read_array()?;
+        Ok(val)
+    }
+
+    fn read_bool(&mut self) -> Result<bool, Error> {
+        let start = self.position();
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            b => Err(Error::new(ErrorKind::InvalidBool(b)).at(start)),
+        }
+    }
+
+    fn read_char(&mut self) -> Result<char, Error> {
+        let start = self.position();
+        let mut arr = [0u8; 4];
+        arr[0] = self.read_u8()?;
+        let len = match arr[0].leading_ones() {
+            0 => 1,
+            n @ 2..=4 => n as usize,
+            _ => return Err(Error::new(ErrorKind::InvalidChar).at(start)),
+        };
+        self.read_into(&mut arr[1..len])?;
+        match Utf8Char::from_slice_start(&arr[..len]) {
+            Ok((c, _)) => Ok(c.to_char()),
+            Err(_) => Err(Error::new(ErrorKind::InvalidChar).at(start)),
+        }
+    }
+
+    /// Read the type marker of the next value, in the self-describing format.
+    #[inline]
+    fn read_marker(&mut self) -> Result<Marker, Error> {
+        let start = self.position();
+        let b = self.read_u8()?;
+        Marker::from_u8(b).ok_or_else(|| Error::new(ErrorKind::InvalidMarker(b)).at(start))
+    }
+
+    /// Read a type marker, failing unless it's `expected`.
+    pub(crate) fn expect_marker(&mut self, expected: Marker) -> Result<(), Error> {
+        let start = self.position();
+        match self.read_marker()? {
+            marker if marker == expected => Ok(()),
+            marker => Err(Error::new(ErrorKind::InvalidMarker(marker as u8)).at(start)),
+        }
+    }
+
+    /// Read whether an `Option` is `Some`, from its tag byte or, in the self-describing format, its
+    /// marker.
+    pub(crate) fn read_option_tag(&mut self) -> Result<bool, Error> {
+        let start = self.position();
+        if O::Format::DESCRIBED {
+            return match self.read_marker()? {
+                Marker::None => Ok(false),
+                Marker::Some => Ok(true),
+                marker => Err(Error::new(ErrorKind::InvalidMarker(marker as u8)).at(start)),
+            };
+        }
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            b => Err(Error::new(ErrorKind::InvalidOptionTag(b)).at(start)),
+        }
+    }
+
+    /// Read a length-prefixed string or byte slice and hand it to `visitor`, borrowed if the input
+    /// allows and copied out otherwise.
+    fn read_bytes<V: Visitor<'de>>(&mut self, utf8: bool, visitor: V) -> Result<V::Value, Error> {
+        let start = self.position();
+        let len = self.read_collection_len()?;
+        let pos = self.position();
+        if let Some(bytes) = self.input.read_borrowed(len).map_err(|e| e.at(pos))? {
+            if !utf8 {
+                return visitor.visit_borrowed_bytes(bytes);
+            }
+            return match core::str::from_utf8(bytes) {
+                Ok(s) => visitor.visit_borrowed_str(s),
+                Err(_) => Err(Error::new(ErrorKind::InvalidUtf8).at(start)),
+            };
+        }
+        self.read_owned_bytes(start, len, utf8, visitor)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn read_owned_bytes<V: Visitor<'de>>(
+        &mut self,
+        start: usize,
+        len: usize,
+        utf8: bool,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        // grow as the bytes arrive rather than trusting the length up front
+        let mut bytes = Vec::new();
+        let mut chunk = [0u8; 256];
+        while bytes.len() < len {
+            let n = (len - bytes.len()).min(chunk.len());
+            self.read_into(&mut chunk[..n])?;
+            bytes.extend_from_slice(&chunk[..n]);
+        }
+        if !utf8 {
+            return visitor.visit_byte_buf(bytes);
+        }
+        match String::from_utf8(bytes) {
+            Ok(s) => visitor.visit_string(s),
+            Err(_) => Err(Error::new(ErrorKind::InvalidUtf8).at(start)),
+        }
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn read_owned_bytes<V: Visitor<'de>>(
+        &mut self,
+        start: usize,
+        _len: usize,
+        _utf8: bool,
+        _visitor: V,
+    ) -> Result<V::Value, Error> {
+        ns().map_err(|e: Error| e.at(start))
+    }
+
+    #[inline]
+    fn read_u16(&mut self) -> Result<u16, Error> {
+        let arr = self.read_array()?;
+        if O::Endian::BIG_ENDIAN {
+            Ok(u16::from_be_bytes(arr))
+        } else {
+            Ok(u16::from_le_bytes(arr))
+        }
+    }
+
+    #[inline]
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        let arr = self.read_array()?;
+        if O::Endian::BIG_ENDIAN {
+            Ok(u32::from_be_bytes(arr))
+        } else {
+            Ok(u32::from_le_bytes(arr))
+        }
+    }
+
+    #[inline]
+    fn read_u64(&mut self) -> Result<u64, Error> {
+        let arr = self.read_array()?;
+        if O::Endian::BIG_ENDIAN {
+            Ok(u64::from_be_bytes(arr))
+        } else {
+            Ok(u64::from_le_bytes(arr))
+        }
+    }
+
+    #[inline]
+    fn read_u128(&mut self) -> Result<u128, Error> {
+        let arr = self.read_array()?;
+        if O::Endian::BIG_ENDIAN {
+            Ok(u128::from_be_bytes(arr))
+        } else {
+            Ok(u128::from_le_bytes(arr))
+        }
+    }
+
+    /// Read a `W`-wide unsigned integer.
+    #[inline]
+    fn read_uint<W: Width>(&mut self) -> Result<u64, Error> {
+        match W::BYTES {
+            1 => self.read_u8().map(|v| v as u64),
+            2 => self.read_u16().map(|v| v as u64),
+            4 => self.read_u32().map(|v| v as u64),
+            _ => self.read_u64(),
+        }
+    }
+
+    #[inline]
+    fn read_usize(&mut self) -> Result<u64, Error> {
+        self.read_uint::<O::Usize>()
+    }
+
+    #[inline]
+    fn read_isize(&mut self) -> Result<i64, Error> {
+        if O::Usize::BYTES == 4 {
+            Ok(self.read_u32()? as i32 as i64)
+        } else {
+            Ok(self.read_u64()? as i64)
+        }
+    }
+
+    #[inline]
+    fn read_len(&mut self) -> Result<usize, Error> {
+        let start = self.position();
+        let len = self.read_uint::<O::Len>()?;
+        if len > usize::MAX as u64 {
+            return Err(Error::new(ErrorKind::Overflow).at(start));
+        }
+        Ok(len as usize)
+    }
+
+    /// Read the length prefix of a sequence, map, string or byte slice, checking it against the
+    /// limits.
+    pub(crate) fn read_collection_len(&mut self) -> Result<usize, Error> {
+        let start = self.position();
+        let len = self.read_len()?;
+        self.check_len(start, len)?;
+        Ok(len)
+    }
+
+    /// Check a length prefix read at `start` against the limits, counting its elements towards
+    /// `max_elements`.
+    fn check_len(&mut self, start: usize, len: usize) -> Result<(), Error> {
+        if len > self.limits.max_len() {
+            return Err(Error::new(ErrorKind::LimitExceeded(Limit::Len)).at(start));
+        }
+        match self.elements.checked_add(len) {
+            Some(elements) if elements <= self.limits.max_elements() => self.elements = elements,
+            _ => return Err(Error::new(ErrorKind::LimitExceeded(Limit::Elements)).at(start)),
+        }
+        Ok(())
+    }
+
+    /// Run `f` one level deeper, failing if that's past the depth limit.
+    #[inline]
+    pub(crate) fn nested<T, F>(&mut self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Self) -> Result<T, Error>,
+    {
+        if self.depth >= self.limits.max_depth() {
+            return Err(Error::new(ErrorKind::LimitExceeded(Limit::Depth)).at(self.position()));
+        }
+        self.depth += 1;
+        let res = f(self);
+        self.depth -= 1;
+        res
+    }
+
+    #[inline]
+    pub(crate) fn read_tag(&mut self) -> Result<u32, Error> {
+        Ok(self.read_uint::<O::Tag>()? as u32)
+    }
+
+    /// Check the prefix of a bounded collection against its capacity, then hand the prefix and
+    /// the elements to `visitor` as one sequence.
+    #[cfg(any(feature = "heapless", feature = "arrayvec"))]
+    fn deserialize_bounded<V: Visitor<'de>>(
+        &mut self,
+        cap: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if O::Format::DESCRIBED {
+            // a sequence of the prefix and then the elements
+            self.expect_marker(Marker::Seq)?;
+            let start = self.position();
+            let len = self.read_collection_len()?;
+            if len.saturating_sub(1) > cap {
+                return Err(Error::new(ErrorKind::CapacityExceeded).at(start));
+            }
+            return self.read_seq(len, None, visitor);
+        }
+        let start = self.position();
+        let len = match ::config::prefix_width(cap) {
+            1 => self.read_u8()? as u32,
+            2 => self.read_u16()? as u32,
+            _ => self.read_u32()?,
+        };
+        if len as usize > cap {
+            return Err(Error::new(ErrorKind::CapacityExceeded).at(start));
+        }
+        self.check_len(start, len as usize)?;
+        self.visit_seq(Some(len), len as usize, None, visitor)
+    }
+
+    /// Hand the next `len` values to `visitor`, checking it reads them all in strict mode.
+    #[inline]
+    fn read_seq<V: Visitor<'de>>(
+        &mut self,
+        len: usize,
+        fields: Option<&'static [&'static str]>,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.visit_seq(None, len, fields, visitor)
+    }
+
+    /// Like `read_seq`, but with an already-read length prefix to hand out first.
+    fn visit_seq<V: Visitor<'de>>(
+        &mut self,
+        prefix: Option<u32>,
+        len: usize,
+        fields: Option<&'static [&'static str]>,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.nested(|de| {
+            let mut access = SeqAccess::new(de, prefix, len, fields);
+            let val = visitor.visit_seq(&mut access)?;
+            if O::Mode::STRICT && access.len > 0 {
+                let idx = access.deserializer.position();
+                return Err(Error::new(ErrorKind::MoreElements).at(idx));
+            }
+            Ok(val)
+        })
+    }
+
+    /// Hand the next `len` entries to `visitor`, checking it reads them all in strict mode.
+    fn read_map<V: Visitor<'de>>(&mut self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.nested(|de| {
+            let mut access = MapAccess {
+                deserializer: de,
+                len,
+                index: 0,
+            };
+            let val = visitor.visit_map(&mut access)?;
+            if O::Mode::STRICT && access.len > 0 {
+                let idx = access.deserializer.position();
+                return Err(Error::new(ErrorKind::MoreElements).at(idx));
+            }
+            Ok(val)
+        })
+    }
+
+    /// Read a float's bits, rejecting non-canonical NaNs in canonical mode.
+    #[inline]
+    fn read_f32(&mut self) -> Result<f32, Error> {
+        let start = self.position();
+        let bits = self.read_u32()?;
+        let val = f32::from_bits(bits);
+        if O::Mode::CANONICAL && val.is_nan() && bits != CANONICAL_NAN_F32 {
+            return Err(Error::new(ErrorKind::NonCanonicalNan).at(start));
+        }
+        Ok(val)
+    }
+
+    #[inline]
+    fn read_f64(&mut self) -> Result<f64, Error> {
+        let start = self.position();
+        let bits = self.read_u64()?;
+        let val = f64::from_bits(bits);
+        if O::Mode::CANONICAL && val.is_nan() && bits != CANONICAL_NAN_F64 {
+            return Err(Error::new(ErrorKind::NonCanonicalNan).at(start));
+        }
+        Ok(val)
+    }
+}
+
+struct SeqAccess<'a, R: 'a, O: 'a> {
+    deserializer: &'a mut Deserializer<R, O>,
+    // the length prefix of a bounded collection, which has been read already
+    prefix: Option<u32>,
+    len: usize,
+    index: usize,
+    // the field names, when this is a struct
+    fields: Option<&'static [&'static str]>,
+}
+
+impl<'a, R: 'a, O: 'a> SeqAccess<'a, R, O> {
+    fn new(
+        deserializer: &'a mut Deserializer<R, O>,
+        prefix: Option<u32>,
+        len: usize,
+        fields: Option<&'static [&'static str]>,
+    ) -> SeqAccess<'a, R, O> {
+        SeqAccess {
+            deserializer,
+            prefix,
+            len,
+            index: prefix.is_some() as usize,
+            fields,
+        }
+    }
+}
+
+impl<'de, 'a, R: Input<'de>, O: Options> serde::de::SeqAccess<'de> for SeqAccess<'a, R, O> {
+    type Error = Error;
+
+    fn next_element_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<Option<V::Value>, Error> {
+        if let Some(prefix) = self.prefix.take() {
+            let prefix = IntoDeserializer::<Error>::into_deserializer(prefix);
+            return seed.deserialize(prefix).map(Some);
+        }
+        if self.len == 0 {
+            return Ok(None);
+        }
+        self.len -= 1;
+        let index = self.index;
+        self.index += 1;
+        let start = self.deserializer.position();
+        match DeserializeSeed::deserialize(seed, &mut *self.deserializer) {
+            Ok(val) => Ok(Some(val)),
+            Err(e) => {
+                let crumb = match self.fields.and_then(|fields| fields.get(index)) {
+                    Some(name) => Crumb::Field(name),
+                    None => Crumb::Index(index),
+                };
+                Err(e.at(start).within(crumb))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len + self.prefix.is_some() as usize)
+    }
+}
+
+struct MapAccess<'a, R: 'a, O: 'a> {
+    deserializer: &'a mut Deserializer<R, O>,
+    len: usize,
+    index: usize,
+}
+
+impl<'de, 'a, R: Input<'de>, O: Options> serde::de::MapAccess<'de> for MapAccess<'a, R, O> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+        self.len -= 1;
+        let start = self.deserializer.position();
+        DeserializeSeed::deserialize(seed, &mut *self.deserializer)
+            .map(Some)
+            .map_err(|e| e.at(start).within(Crumb::Index(self.index)))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let index = self.index;
+        self.index += 1;
+        let start = self.deserializer.position();
+        DeserializeSeed::deserialize(seed, &mut *self.deserializer)
+            .map_err(|e| e.at(start).within(Crumb::Index(index)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+
+type DeserializeResult<T> = Result<T, Error>;
+
+impl<'de, R: Input<'de>, O: Options> serde::Deserializer<'de> for &mut Deserializer<R, O> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if !O::Format::DESCRIBED {
+            return ns();
+        }
+        let start = self.position();
+        match self.read_marker()? {
+            Marker::Unit => visitor.visit_unit(),
+            Marker::Bool => visitor.visit_bool(self.read_bool()?),
+            Marker::U8 => visitor.visit_u8(self.read_u8()?),
+            Marker::U16 => visitor.visit_u16(self.read_u16()?),
+            Marker::U32 => visitor.visit_u32(self.read_u32()?),
+            Marker::U64 => visitor.visit_u64(self.read_u64()?),
+            Marker::U128 => visitor.visit_u128(self.read_u128()?),
+            Marker::I8 => visitor.visit_i8(self.read_u8()? as i8),
+            Marker::I16 => visitor.visit_i16(self.read_u16()? as i16),
+            Marker::I32 => visitor.visit_i32(self.read_u32()? as i32),
+            Marker::I64 => visitor.visit_i64(self.read_u64()? as i64),
+            Marker::I128 => visitor.visit_i128(self.read_u128()? as i128),
+            Marker::F32 => visitor.visit_f32(self.read_f32()?),
+            Marker::F64 => visitor.visit_f64(self.read_f64()?),
+            Marker::Char => visitor.visit_char(self.read_char()?),
+            Marker::Str => self.read_bytes(true, visitor),
+            Marker::Bytes => self.read_bytes(false, visitor),
+            Marker::None => visitor.visit_none(),
+            Marker::Some => self.nested(|de| visitor.visit_some(de)),
+            Marker::Seq => {
+                let len = self.read_collection_len()?;
+                self.read_seq(len, None, visitor)
+            }
+            Marker::Map if O::Mode::CANONICAL => {
+                Err(Error::new(ErrorKind::NonCanonicalMap).at(start))
+            }
+            Marker::Map => {
+                let len = self.read_collection_len()?;
+                self.read_map(len, visitor)
+            }
+            // without the type, an enum looks like a map from the variant's name to its contents
+            Marker::Enum => {
+                self.read_tag()?;
+                self.read_map(1, visitor)
+            }
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        visitor.visit_bool(self.read_bool()?)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        visitor.visit_u8(self.read_u8()?)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        visitor.visit_u16(self.read_u16()?)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        visitor.visit_u32(self.read_u32()?)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        visitor.visit_u64(self.read_u64()?)
+    }
+
+    fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        visitor.visit_u128(self.read_u128()?)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        visitor.visit_i8(self.read_u8()? as i8)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        visitor.visit_i16(self.read_u16()? as i16)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        visitor.visit_i32(self.read_u32()? as i32)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        visitor.visit_i64(self.read_u64()? as i64)
+    }
+
+    fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        visitor.visit_i128(self.read_u128()? as i128)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        visitor.visit_f32(self.read_f32()?)
+    }
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        visitor.visit_f64(self.read_f64()?)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        visitor.visit_char(self.read_char()?)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        self.read_bytes(true, visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        self.read_bytes(false, visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        if self.read_option_tag()? {
+            self.nested(|de| visitor.visit_some(de))
+        } else {
+            visitor.visit_none()
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &str,
+        visitor: V,
+    ) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.nested(|de| visitor.visit_newtype_struct(de));
+        }
+        if name == word::USIZE_TOKEN {
+            let val = self.read_usize()?;
+            return visitor.visit_newtype_struct(val.into_deserializer());
+        }
+        if name == word::ISIZE_TOKEN {
+            let val = self.read_isize()?;
+            return visitor.visit_newtype_struct(val.into_deserializer());
+        }
+        self.nested(|de| visitor.visit_newtype_struct(de))
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        let len = self.read_collection_len()?;
+        self.read_seq(len, None, visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        self.read_seq(len, None, visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> DeserializeResult<V::Value> {
+        #[cfg(any(feature = "heapless", feature = "arrayvec"))]
+        {
+            if name == bounded::BOUNDED_TOKEN {
+                return self.deserialize_bounded(len.saturating_sub(1), visitor);
+            }
+        }
+        #[cfg(not(any(feature = "heapless", feature = "arrayvec")))]
+        let _ = name;
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Mode::CANONICAL {
+            return Err(Error::new(ErrorKind::NonCanonicalMap).at(self.position()));
+        }
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        let len = self.read_collection_len()?;
+        self.read_map(len, visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        self.read_seq(fields.len(), Some(fields), visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _enum: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if O::Format::DESCRIBED {
+            self.expect_marker(Marker::Enum)?;
+        }
+        visitor.visit_enum(Enum {
+            deserializer: self,
+            variants,
+        })
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        // not the panic because it seems noone cares about these?
+        Err(Error::new(ErrorKind::NotSupported).at(self.position()))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> DeserializeResult<V::Value> {
+        if O::Format::DESCRIBED {
+            return self.deserialize_any(visitor);
+        }
+        ns()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+struct Enum<'a, R: 'a, O: 'a> {
+    deserializer: &'a mut Deserializer<R, O>,
+    variants: &'static [&'static str],
+}
+
+impl<'de, 'a, R: Input<'de>, O: Options> serde::de::EnumAccess<'de> for Enum<'a, R, O> {
+    type Error = Error;
+    type Variant = Variant<'a, R, O>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> DeserializeResult<(V::Value, Variant<'a, R, O>)> {
+        let start = self.deserializer.position();
+        let index = self.deserializer.read_tag()?;
+        let name = match self.variants.get(index as usize) {
+            Some(name) => name,
+            None => return Err(Error::new(ErrorKind::UnknownVariant { index }).at(start)),
+        };
+        if O::Format::DESCRIBED {
+            // the name is only there for `deserialize_any`
+            IgnoredAny::deserialize(&mut *self.deserializer)?;
+        }
+        let v = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(index))?;
+        Ok((
+            v,
+            Variant {
+                deserializer: self.deserializer,
+                name,
+            },
+        ))
+    }
+}
+
+struct Variant<'a, R: 'a, O: 'a> {
+    deserializer: &'a mut Deserializer<R, O>,
+    name: &'static &'static str,
+}
+
+impl<'de, 'a, R: Input<'de>, O: Options> serde::de::VariantAccess<'de> for Variant<'a, R, O> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        if O::Format::DESCRIBED {
+            self.deserializer.expect_marker(Marker::Unit)?;
+        }
+        Ok(())
+    }
+
+    fn newtype_variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> DeserializeResult<V::Value> {
+        let name = self.name;
+        self.deserializer
+            .nested(|de| DeserializeSeed::deserialize(seed, de))
+            .map_err(|e| e.within(Crumb::Variant(name)))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> DeserializeResult<V::Value> {
+        let res = if O::Format::DESCRIBED {
+            serde::Deserializer::deserialize_tuple(&mut *self.deserializer, len, visitor)
+        } else {
+            self.deserializer.read_seq(len, None, visitor)
+        };
+        res.map_err(|e| e.within(Crumb::Variant(self.name)))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let res = if O::Format::DESCRIBED {
+            serde::Deserializer::deserialize_struct(&mut *self.deserializer, "", fields, visitor)
+        } else {
+            self.deserializer
+                .read_seq(fields.len(), Some(fields), visitor)
+        };
+        res.map_err(|e| e.within(Crumb::Variant(self.name)))
+    }
+}
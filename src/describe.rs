@@ -0,0 +1,69 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+//! The type markers of the `SelfDescribing` format.
+
+/// The byte written before each value in the self-describing format, saying what follows it.
+///
+/// Values are otherwise encoded as in the bare format, except that tuples have a length prefix
+/// like sequences, structs are maps from field names to values, and enum discriminants are
+/// followed by the variant name as a string, then the contents.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub(crate) enum Marker {
+    Unit = 0x00,
+    Bool = 0x01,
+    U8 = 0x02,
+    U16 = 0x03,
+    U32 = 0x04,
+    U64 = 0x05,
+    U128 = 0x06,
+    I8 = 0x07,
+    I16 = 0x08,
+    I32 = 0x09,
+    I64 = 0x0A,
+    I128 = 0x0B,
+    F32 = 0x0C,
+    F64 = 0x0D,
+    Char = 0x0E,
+    Str = 0x0F,
+    Bytes = 0x10,
+    None = 0x11,
+    Some = 0x12,
+    Seq = 0x13,
+    Map = 0x14,
+    Enum = 0x15,
+}
+
+impl Marker {
+    pub(crate) fn from_u8(b: u8) -> Option<Marker> {
+        Some(match b {
+            0x00 => Marker::Unit,
+            0x01 => Marker::Bool,
+            0x02 => Marker::U8,
+            0x03 => Marker::U16,
+            0x04 => Marker::U32,
+            0x05 => Marker::U64,
+            0x06 => Marker::U128,
+            0x07 => Marker::I8,
+            0x08 => Marker::I16,
+            0x09 => Marker::I32,
+            0x0A => Marker::I64,
+            0x0B => Marker::I128,
+            0x0C => Marker::F32,
+            0x0D => Marker::F64,
+            0x0E => Marker::Char,
+            0x0F => Marker::Str,
+            0x10 => Marker::Bytes,
+            0x11 => Marker::None,
+            0x12 => Marker::Some,
+            0x13 => Marker::Seq,
+            0x14 => Marker::Map,
+            0x15 => Marker::Enum,
+            _ => return None,
+        })
+    }
+}
@@ -0,0 +1,1265 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+//! Decoding and encoding values of types known only from a `Schema`.
+//!
+//! `decode` reads a `Value` out of a buffer by following the schema, and `encode` writes one
+//! back. Both drive the same `Deserializer` and `Serializer` as the typed functions, with the
+//! options recorded in `Schema::encoding`, so they accept and produce exactly the same bytes and
+//! fail with the same errors at the same offsets. Errors don't carry a path, though, since field
+//! and variant names come from the schema rather than from the type.
+//!
+//! `decode_with_offsets` also returns the byte range each value was decoded from, for showing
+//! where the parts of a capture lie, and `to_vec` encodes into a new vector.
+//!
+//! `Value` is serializable, so with `std` a decoded value can be written as JSON with
+//! `Value::to_json`, and it can be deserialized from self-describing formats like JSON, as
+//! `Value::from_json` does. Such a value doesn't know its exact types, so `encode` accepts any
+//! value which fits the schema: an integer of any width in range, a map with the field names for a
+//! struct, a string naming a unit variant or a single-entry map for an enum, and `Unit` for `None`.
+//!
+//! ```rust
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate ssmarshal;
+//! use ssmarshal::dynamic::{self, Value};
+//! use ssmarshal::schema;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Point {
+//!     x: i16,
+//!     y: Option<u8>,
+//! }
+//!
+//! # fn main() {
+//! let schema = schema::trace::<Point>().unwrap();
+//! let mut buf = [0u8; 8];
+//! let len = ssmarshal::serialize(&mut buf, &Point { x: -1, y: Some(2) }).unwrap();
+//!
+//! let (val, _) = dynamic::decode(&schema, &buf[..len]).unwrap();
+//! assert_eq!(
+//!     val,
+//!     Value::Struct(vec![
+//!         ("x".into(), Value::I16(-1)),
+//!         ("y".into(), Value::Option(Some(Box::new(Value::U8(2))))),
+//!     ])
+//! );
+//!
+//! let mut out = [0u8; 8];
+//! assert_eq!(dynamic::encode(&schema, &val, &mut out).unwrap(), len);
+//! assert_eq!(out, buf);
+//! # }
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+use core::ops::Range;
+
+use serde::de::{self, Deserialize, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq};
+use serde::Serializer as S;
+#[cfg(feature = "std")]
+use serde_json::{self, value::RawValue};
+
+use config::{prefix_width, Canonical, SelfDescribing, TagWidth, UsizeWidth, U16, U32, U64, U8};
+use config::{BigEndian, ByteOrder, Config, Format as _, LenWidth, Lenient, Mode as _, Options};
+use describe::Marker;
+use input::Input;
+use output::{Output, SliceOutput};
+#[cfg(feature = "std")]
+use schema::json_error;
+use schema::{custom, Container, Encoding, Field, Format, Schema, VariantFormat};
+use {word, Deserializer, Error, ErrorKind, Limits, Serializer};
+
+/// A value of any type, as decoded from a `Schema`.
+///
+/// Tuples, arrays and bounded collections are all `Seq`s, newtype structs are their contents, and
+/// unit structs are `Unit`. `usize` and `isize` words are `U64` and `I64`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Unit,
+    Bool(bool),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    U128(u128),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    I128(i128),
+    F32(f32),
+    F64(f64),
+    Char(char),
+    Str(String),
+    Bytes(Vec<u8>),
+    Option(Option<Box<Value>>),
+    Seq(Vec<Value>),
+    /// The entries of a map, in the order they were encoded.
+    Map(Vec<(Value, Value)>),
+    /// The fields of a struct or struct variant, by name.
+    Struct(Vec<(String, Value)>),
+    /// An enum variant and its contents: `Unit`, the newtype's value, a `Seq` or a `Struct`.
+    Variant(String, Box<Value>),
+}
+
+impl Value {
+    /// Write the value as JSON, indented if `pretty`.
+    ///
+    /// Enums are written as serde_json writes them: a unit variant as its name, and other
+    /// variants as a map from the name to the contents. Map keys which aren't strings are written
+    /// as the string of their JSON, and floats which JSON can't represent as the strings `"NaN"`,
+    /// `"inf"` and `"-inf"`.
+    #[cfg(feature = "std")]
+    pub fn to_json(&self, pretty: bool) -> String {
+        let json = if pretty {
+            serde_json::to_string_pretty(&Json(self))
+        } else {
+            serde_json::to_string(&Json(self))
+        };
+        json.expect("values are always valid JSON")
+    }
+
+    /// Read a value from JSON. Integers become `U64` or `I64`, or `U128` or `I128` if they don't
+    /// fit, and other numbers `F64`. `null` becomes `Unit`, arrays `Seq` and objects `Map`, which
+    /// [`encode`](fn.encode.html) accepts in place of the exact types, as it accepts the strings
+    /// `"NaN"`, `"inf"` and `"-inf"` for floats.
+    #[cfg(feature = "std")]
+    pub fn from_json(text: &str) -> Result<Value, Error> {
+        let raw = serde_json::from_str(text).map_err(|e| json_error(text, &e))?;
+        from_raw_json(text, raw, 0)
+    }
+
+    /// What kind of value this is, for error messages.
+    fn kind(&self) -> &'static str {
+        match *self {
+            Value::Unit => "unit",
+            Value::Bool(_) => "a bool",
+            Value::U8(_)
+            | Value::U16(_)
+            | Value::U32(_)
+            | Value::U64(_)
+            | Value::U128(_)
+            | Value::I8(_)
+            | Value::I16(_)
+            | Value::I32(_)
+            | Value::I64(_)
+            | Value::I128(_) => "an integer",
+            Value::F32(_) | Value::F64(_) => "a float",
+            Value::Char(_) => "a char",
+            Value::Str(_) => "a string",
+            Value::Bytes(_) => "bytes",
+            Value::Option(_) => "an option",
+            Value::Seq(_) => "a sequence",
+            Value::Map(_) => "a map",
+            Value::Struct(_) => "a struct",
+            Value::Variant(..) => "an enum variant",
+        }
+    }
+
+    /// The value as an integer, if it's one.
+    fn integer(&self) -> Option<Integer> {
+        Some(match *self {
+            Value::U8(v) => Integer::Unsigned(v.into()),
+            Value::U16(v) => Integer::Unsigned(v.into()),
+            Value::U32(v) => Integer::Unsigned(v.into()),
+            Value::U64(v) => Integer::Unsigned(v.into()),
+            Value::U128(v) => Integer::Unsigned(v),
+            Value::I8(v) => Integer::Signed(v.into()),
+            Value::I16(v) => Integer::Signed(v.into()),
+            Value::I32(v) => Integer::Signed(v.into()),
+            Value::I64(v) => Integer::Signed(v.into()),
+            Value::I128(v) => Integer::Signed(v),
+            _ => return None,
+        })
+    }
+
+    fn float(&self) -> Option<f64> {
+        match *self {
+            Value::F32(v) => Some(v.into()),
+            Value::F64(v) => Some(v),
+            // JSON writes non-finite floats as strings
+            Value::Str(ref s) => s.parse().ok().filter(|v: &f64| !v.is_finite()),
+            _ => match self.integer()? {
+                Integer::Unsigned(v) => Some(v as f64),
+                Integer::Signed(v) => Some(v as f64),
+            },
+        }
+    }
+
+    /// The elements of a sequence, tuple or array.
+    fn elements(&self) -> Option<Cow<'_, [Value]>> {
+        match *self {
+            Value::Seq(ref elements) => Some(Cow::Borrowed(elements)),
+            Value::Bytes(ref bytes) => {
+                Some(Cow::Owned(bytes.iter().map(|&b| Value::U8(b)).collect()))
+            }
+            _ => None,
+        }
+    }
+
+    /// The field of a struct called `name`.
+    fn field(&self, name: &str) -> Option<&Value> {
+        match *self {
+            Value::Struct(ref fields) => fields.iter().find(|f| f.0 == name).map(|f| &f.1),
+            Value::Map(ref entries) => entries
+                .iter()
+                .find(|e| matches!(e.0, Value::Str(ref key) if key == name))
+                .map(|e| &e.1),
+            _ => None,
+        }
+    }
+
+    /// The name and contents of an enum variant.
+    fn variant(&self) -> Option<(&str, Cow<'_, Value>)> {
+        match *self {
+            Value::Variant(ref name, ref contents) => Some((name, Cow::Borrowed(contents))),
+            Value::Str(ref name) => Some((name, Cow::Owned(Value::Unit))),
+            Value::Map(ref entries) if entries.len() == 1 => match entries[0] {
+                (Value::Str(ref name), ref contents) => Some((name, Cow::Borrowed(contents))),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+enum Integer {
+    Unsigned(u128),
+    Signed(i128),
+}
+
+/// Decode a value of the schema's root type from the start of `buf`. Returns the value and the
+/// number of bytes used.
+pub fn decode(schema: &Schema, buf: &[u8]) -> Result<(Value, usize), Error> {
+    decode_limited(schema, buf, Limits::new())
+}
+
+/// Like `decode`, but failing with `ErrorKind::LimitExceeded` if the value goes past `limits`.
+/// Use this for untrusted input when the schema is recursive.
+pub fn decode_limited(
+    schema: &Schema,
+    buf: &[u8],
+    limits: Limits,
+) -> Result<(Value, usize), Error> {
+    let decode = Decode {
+        schema,
+        buf,
+        limits,
+        offsets: None,
+    };
+    with_options(&schema.encoding, decode)
+}
+
+/// Like `decode`, but also returning the range of bytes each value in it was decoded from.
+///
+/// The ranges are listed in the order a depth-first walk of the value reaches them: a value
+/// comes before its contents, which are the elements of a `Seq`, the key then the value of each
+/// entry of a `Map`, the fields of a `Struct`, the value inside `Some`, and the contents of a
+/// `Variant`.
+pub fn decode_with_offsets(
+    schema: &Schema,
+    buf: &[u8],
+) -> Result<(Value, Vec<Range<usize>>), Error> {
+    decode_with_offsets_limited(schema, buf, Limits::new())
+}
+
+/// Like `decode_with_offsets`, but failing with `ErrorKind::LimitExceeded` if the value goes past
+/// `limits`.
+pub fn decode_with_offsets_limited(
+    schema: &Schema,
+    buf: &[u8],
+    limits: Limits,
+) -> Result<(Value, Vec<Range<usize>>), Error> {
+    let mut offsets = Vec::new();
+    let decode = Decode {
+        schema,
+        buf,
+        limits,
+        offsets: Some(&mut offsets),
+    };
+    let (val, _) = with_options(&schema.encoding, decode)?;
+    Ok((val, offsets))
+}
+
+/// Encode `val` as a value of the schema's root type into `buf`. Returns the number of bytes
+/// used.
+pub fn encode(schema: &Schema, val: &Value, buf: &mut [u8]) -> Result<usize, Error> {
+    let encode = Encode {
+        schema,
+        val,
+        out: SliceOutput::new(buf),
+    };
+    Ok(with_options(&schema.encoding, encode)?.0)
+}
+
+/// Encode `val` as a value of the schema's root type into a newly allocated vector of exactly
+/// the right size.
+pub fn to_vec(schema: &Schema, val: &Value) -> Result<Vec<u8>, Error> {
+    let encode = Encode {
+        schema,
+        val,
+        out: Vec::new(),
+    };
+    Ok(with_options(&schema.encoding, encode)?.1)
+}
+
+/// Something to do with the `Options` matching an `Encoding`.
+trait WithOptions {
+    type Output;
+
+    fn run<O: Options>(self, opts: O) -> Result<Self::Output, Error>;
+}
+
+/// Run `task` with the options matching `encoding`, choosing one option at a time.
+fn with_options<T: WithOptions>(encoding: &Encoding, task: T) -> Result<T::Output, Error> {
+    if encoding.big_endian {
+        with_usize(encoding, Config::new().with_endian::<BigEndian>(), task)
+    } else {
+        with_usize(encoding, Config::new(), task)
+    }
+}
+
+fn with_usize<E: ByteOrder, T: WithOptions>(
+    encoding: &Encoding,
+    opts: Config<E>,
+    task: T,
+) -> Result<T::Output, Error> {
+    match encoding.usize_width {
+        4 => with_len(encoding, opts.with_usize::<U32>(), task),
+        8 => with_len(encoding, opts.with_usize::<U64>(), task),
+        w => Err(custom(format_args!("unsupported usize width {}", w))),
+    }
+}
+
+fn with_len<E: ByteOrder, S: UsizeWidth, T: WithOptions>(
+    encoding: &Encoding,
+    opts: Config<E, S>,
+    task: T,
+) -> Result<T::Output, Error> {
+    match encoding.len_width {
+        1 => with_tag(encoding, opts.with_len::<U8>(), task),
+        2 => with_tag(encoding, opts.with_len::<U16>(), task),
+        4 => with_tag(encoding, opts.with_len::<U32>(), task),
+        8 => with_tag(encoding, opts.with_len::<U64>(), task),
+        w => Err(custom(format_args!("unsupported length width {}", w))),
+    }
+}
+
+fn with_tag<E: ByteOrder, S: UsizeWidth, L: LenWidth, T: WithOptions>(
+    encoding: &Encoding,
+    opts: Config<E, S, L>,
+    task: T,
+) -> Result<T::Output, Error> {
+    match encoding.tag_width {
+        1 => with_format(encoding, opts.with_tag::<U8>(), task),
+        2 => with_format(encoding, opts.with_tag::<U16>(), task),
+        4 => with_format(encoding, opts.with_tag::<U32>(), task),
+        w => Err(custom(format_args!("unsupported tag width {}", w))),
+    }
+}
+
+fn with_format<E: ByteOrder, S: UsizeWidth, L: LenWidth, G: TagWidth, T: WithOptions>(
+    encoding: &Encoding,
+    opts: Config<E, S, L, G>,
+    task: T,
+) -> Result<T::Output, Error> {
+    if encoding.self_describing {
+        with_mode(encoding, opts.with_format::<SelfDescribing>(), task)
+    } else {
+        with_mode(encoding, opts, task)
+    }
+}
+
+fn with_mode<E, S, L, G, F, T>(
+    encoding: &Encoding,
+    opts: Config<E, S, L, G, Lenient, F>,
+    task: T,
+) -> Result<T::Output, Error>
+where
+    E: ByteOrder,
+    S: UsizeWidth,
+    L: LenWidth,
+    G: TagWidth,
+    F: ::config::Format,
+    T: WithOptions,
+{
+    if encoding.canonical {
+        task.run(opts.with_mode::<Canonical>())
+    } else {
+        task.run(opts)
+    }
+}
+
+struct Decode<'a> {
+    schema: &'a Schema,
+    buf: &'a [u8],
+    limits: Limits,
+    offsets: Option<&'a mut Vec<Range<usize>>>,
+}
+
+impl<'a> WithOptions for Decode<'a> {
+    type Output = (Value, usize);
+
+    fn run<O: Options>(self, opts: O) -> Result<(Value, usize), Error> {
+        let mut de = Deserializer::with_options(self.buf, opts).with_limits(self.limits);
+        let val = Decoder {
+            schema: self.schema,
+            de: &mut de,
+            offsets: self.offsets,
+        }
+        .format(&self.schema.root)
+        .map_err(|e| e.at(de.position()))?;
+        Ok((val, de.position()))
+    }
+}
+
+/// Reads values by following a schema.
+struct Decoder<'a, 'b, R: 'b, O: 'b> {
+    schema: &'a Schema,
+    de: &'b mut Deserializer<R, O>,
+    /// Where to record the range of bytes each value is decoded from.
+    offsets: Option<&'b mut Vec<Range<usize>>>,
+}
+
+macro_rules! decode_primitive {
+    ($self:ident, $format:expr, $($name:ident($ty:ty)),*) => {
+        match *$format {
+            $(Format::$name => return Ok(Value::$name(<$ty>::deserialize(&mut *$self.de)?)),)*
+            _ => {}
+        }
+    }
+}
+
+impl<'a, 'b, 'de, R: Input<'de>, O: Options> Decoder<'a, 'b, R, O> {
+    fn format(&mut self, format: &Format) -> Result<Value, Error> {
+        self.spanned(|d| d.value(format))
+    }
+
+    /// Decode a value with `f`, recording the bytes it took.
+    fn spanned<F>(&mut self, f: F) -> Result<Value, Error>
+    where
+        F: FnOnce(&mut Self) -> Result<Value, Error>,
+    {
+        let start = self.de.position();
+        let index = match self.offsets {
+            Some(ref mut offsets) => {
+                offsets.push(start..start);
+                offsets.len() - 1
+            }
+            None => return f(self),
+        };
+        let val = f(self)?;
+        let end = self.de.position();
+        if let Some(ref mut offsets) = self.offsets {
+            offsets[index].end = end;
+        }
+        Ok(val)
+    }
+
+    /// Decode a value without recording its bytes, since it's part of one which already is.
+    fn value(&mut self, format: &Format) -> Result<Value, Error> {
+        decode_primitive!(
+            self,
+            format,
+            Bool(bool),
+            U8(u8),
+            U16(u16),
+            U32(u32),
+            U64(u64),
+            U128(u128),
+            I8(i8),
+            I16(i16),
+            I32(i32),
+            I64(i64),
+            I128(i128),
+            F32(f32),
+            F64(f64),
+            Char(char),
+            Str(String)
+        );
+        Ok(match *format {
+            Format::Unit => {
+                self.unit()?;
+                Value::Unit
+            }
+            Format::Bytes => Value::Bytes(serde::Deserializer::deserialize_bytes(
+                &mut *self.de,
+                BytesVisitor,
+            )?),
+            // at the configured width, even if it's wider than this host's `usize`
+            Format::Usize => Value::U64(serde::Deserializer::deserialize_newtype_struct(
+                &mut *self.de,
+                word::USIZE_TOKEN,
+                UsizeVisitor,
+            )?),
+            Format::Isize => Value::I64(serde::Deserializer::deserialize_newtype_struct(
+                &mut *self.de,
+                word::ISIZE_TOKEN,
+                IsizeVisitor,
+            )?),
+            Format::Option(ref inner) => {
+                if self.de.read_option_tag()? {
+                    let val = self.nested(|d| d.format(inner))?;
+                    Value::Option(Some(Box::new(val)))
+                } else {
+                    Value::Option(None)
+                }
+            }
+            Format::Seq(ref element) => {
+                let len = self.len(Marker::Seq)?;
+                Value::Seq(self.nested(|d| (0..len).map(|_| d.format(element)).collect())?)
+            }
+            Format::Map { ref key, ref value } => {
+                let len = self.len(Marker::Map)?;
+                Value::Map(self.nested(|d| {
+                    (0..len)
+                        .map(|_| Ok((d.format(key)?, d.format(value)?)))
+                        .collect()
+                })?)
+            }
+            Format::Tuple(ref formats) => Value::Seq(self.tuple(formats)?),
+            Format::Array { ref element, len } => {
+                self.fixed(Marker::Seq, len)?;
+                Value::Seq(self.nested(|d| (0..len).map(|_| d.format(element)).collect())?)
+            }
+            Format::Bounded {
+                ref element,
+                capacity,
+            } => Value::Seq(self.bounded(element, capacity)?),
+            Format::Named(ref name) => self.named(name)?,
+            _ => unreachable!("primitives are decoded above"),
+        })
+    }
+
+    fn nested<T, F>(&mut self, f: F) -> Result<T, Error>
+    where
+        F: for<'c> FnOnce(&mut Decoder<'a, 'c, R, O>) -> Result<T, Error>,
+    {
+        let schema = self.schema;
+        let offsets = self.offsets.as_deref_mut();
+        self.de.nested(|de| {
+            f(&mut Decoder {
+                schema,
+                de,
+                offsets,
+            })
+        })
+    }
+
+    fn unit(&mut self) -> Result<(), Error> {
+        if O::Format::DESCRIBED {
+            self.de.expect_marker(Marker::Unit)?;
+        }
+        Ok(())
+    }
+
+    /// Read the length of a sequence or map.
+    fn len(&mut self, marker: Marker) -> Result<usize, Error> {
+        if O::Mode::CANONICAL && marker == Marker::Map {
+            return Err(Error::new(ErrorKind::NonCanonicalMap).at(self.de.position()));
+        }
+        if O::Format::DESCRIBED {
+            self.de.expect_marker(marker)?;
+        }
+        self.de.read_collection_len()
+    }
+
+    /// Read the marker and length of a tuple or struct of `len` elements, if they have any.
+    fn fixed(&mut self, marker: Marker, len: usize) -> Result<(), Error> {
+        if O::Format::DESCRIBED {
+            let start = self.de.position();
+            let found = self.len(marker)?;
+            if found != len {
+                return Err(
+                    custom(format_args!("expected {} elements, found {}", len, found)).at(start),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn tuple(&mut self, formats: &[Format]) -> Result<Vec<Value>, Error> {
+        self.fixed(Marker::Seq, formats.len())?;
+        self.nested(|d| formats.iter().map(|f| d.format(f)).collect())
+    }
+
+    fn fields(&mut self, fields: &[Field]) -> Result<Value, Error> {
+        self.fixed(Marker::Map, fields.len())?;
+        let values = self.nested(|d| {
+            fields
+                .iter()
+                .map(|f| {
+                    if O::Format::DESCRIBED {
+                        IgnoredAny::deserialize(&mut *d.de)?;
+                    }
+                    Ok((f.name.clone(), d.format(&f.format)?))
+                })
+                .collect::<Result<_, Error>>()
+        })?;
+        Ok(Value::Struct(values))
+    }
+
+    fn bounded(&mut self, element: &Format, capacity: usize) -> Result<Vec<Value>, Error> {
+        // the prefix is the first element of a tuple in the self-describing format
+        let total = if O::Format::DESCRIBED {
+            Some(self.len(Marker::Seq)?)
+        } else {
+            None
+        };
+        let start = self.de.position();
+        let len = match prefix_width(capacity) {
+            1 => u8::deserialize(&mut *self.de)? as usize,
+            2 => u16::deserialize(&mut *self.de)? as usize,
+            _ => u32::deserialize(&mut *self.de)? as usize,
+        };
+        if len > capacity || total.is_some_and(|total| total != len + 1) {
+            return Err(Error::new(ErrorKind::CapacityExceeded).at(start));
+        }
+        self.nested(|d| (0..len).map(|_| d.format(element)).collect())
+    }
+
+    fn named(&mut self, name: &str) -> Result<Value, Error> {
+        match *container(self.schema, name)? {
+            Container::UnitStruct => {
+                self.unit()?;
+                Ok(Value::Unit)
+            }
+            Container::NewtypeStruct(ref inner) => self.nested(|d| d.value(inner)),
+            Container::TupleStruct(ref formats) => Ok(Value::Seq(self.tuple(formats)?)),
+            Container::Struct(ref fields) => self.fields(fields),
+            Container::Enum(ref variants) => {
+                if O::Format::DESCRIBED {
+                    self.de.expect_marker(Marker::Enum)?;
+                }
+                let start = self.de.position();
+                let index = self.de.read_tag()?;
+                let variant = match variants.get(index as usize) {
+                    Some(variant) => variant,
+                    None => return Err(Error::new(ErrorKind::UnknownVariant { index }).at(start)),
+                };
+                if O::Format::DESCRIBED {
+                    IgnoredAny::deserialize(&mut *self.de)?;
+                }
+                let contents = match variant.contents {
+                    VariantFormat::Unit => self.spanned(|d| {
+                        d.unit()?;
+                        Ok(Value::Unit)
+                    })?,
+                    VariantFormat::Newtype(ref inner) => self.nested(|d| d.format(inner))?,
+                    VariantFormat::Tuple(ref formats) => {
+                        self.spanned(|d| Ok(Value::Seq(d.tuple(formats)?)))?
+                    }
+                    VariantFormat::Struct(ref fields) => self.spanned(|d| d.fields(fields))?,
+                };
+                Ok(Value::Variant(variant.name.clone(), Box::new(contents)))
+            }
+        }
+    }
+}
+
+fn container<'a>(schema: &'a Schema, name: &str) -> Result<&'a Container, Error> {
+    schema
+        .types
+        .get(name)
+        .ok_or_else(|| custom(format_args!("`{}` isn't in the schema", name)))
+}
+
+/// Reads a `usize` word as a `u64`, without `word::deserialize`'s check that it fits the host.
+struct UsizeVisitor;
+
+impl<'de> Visitor<'de> for UsizeVisitor {
+    type Value = u64;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a usize")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<u64, E> {
+        Ok(v)
+    }
+
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(self, d: D) -> Result<u64, D::Error> {
+        d.deserialize_u64(self)
+    }
+}
+
+/// Reads an `isize` word as an `i64`, without `word::deserialize`'s check that it fits the host.
+struct IsizeVisitor;
+
+impl<'de> Visitor<'de> for IsizeVisitor {
+    type Value = i64;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an isize")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<i64, E> {
+        Ok(v)
+    }
+
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(self, d: D) -> Result<i64, D::Error> {
+        d.deserialize_i64(self)
+    }
+}
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("bytes")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+        Ok(v.into())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+        Ok(v)
+    }
+}
+
+struct Encode<'a, W> {
+    schema: &'a Schema,
+    val: &'a Value,
+    out: W,
+}
+
+impl<'a, W: Output> WithOptions for Encode<'a, W> {
+    type Output = (usize, W);
+
+    fn run<O: Options>(self, opts: O) -> Result<(usize, W), Error> {
+        let mut ser = Serializer::from_output(self.out, opts);
+        Encoder {
+            schema: self.schema,
+            ser: &mut ser,
+        }
+        .format(&self.schema.root, self.val)
+        .map_err(|e| e.at(ser.position()))?;
+        Ok((ser.position(), ser.into_output()))
+    }
+}
+
+/// Writes values by following a schema.
+struct Encoder<'a, 'b, W: 'b, O: 'b> {
+    schema: &'a Schema,
+    ser: &'b mut Serializer<W, O>,
+}
+
+fn serialize_usize<S: ser::Serializer>(serializer: S, v: u64) -> Result<S::Ok, S::Error> {
+    serializer.serialize_newtype_struct(word::USIZE_TOKEN, &v)
+}
+
+fn serialize_isize<S: ser::Serializer>(serializer: S, v: i64) -> Result<S::Ok, S::Error> {
+    serializer.serialize_newtype_struct(word::ISIZE_TOKEN, &v)
+}
+
+fn mismatch(format: &Format, val: &Value) -> Error {
+    custom(format_args!("expected {:?}, found {}", format, val.kind()))
+}
+
+fn out_of_range<T: fmt::Display>(format: &Format, v: T) -> Error {
+    custom(format_args!("{} is out of range for {:?}", v, format))
+}
+
+macro_rules! encode_integer {
+    ($self:ident, $format:expr, $val:expr, $($name:ident($ty:ty) => $method:path),*) => {
+        match *$format {
+            $(Format::$name => {
+                let v = match $val.integer() {
+                    Some(Integer::Unsigned(v)) => {
+                        <$ty>::try_from(v).map_err(|_| out_of_range($format, v))?
+                    }
+                    Some(Integer::Signed(v)) => {
+                        <$ty>::try_from(v).map_err(|_| out_of_range($format, v))?
+                    }
+                    None => return Err(mismatch($format, $val)),
+                };
+                return $method(&mut *$self.ser, v);
+            })*
+            _ => {}
+        }
+    }
+}
+
+impl<'a, 'b, W: Output, O: Options> Encoder<'a, 'b, W, O> {
+    fn format(&mut self, format: &Format, val: &Value) -> Result<(), Error> {
+        encode_integer!(
+            self, format, val,
+            U8(u8) => S::serialize_u8, U16(u16) => S::serialize_u16,
+            U32(u32) => S::serialize_u32, U64(u64) => S::serialize_u64,
+            U128(u128) => S::serialize_u128, I8(i8) => S::serialize_i8,
+            I16(i16) => S::serialize_i16, I32(i32) => S::serialize_i32,
+            I64(i64) => S::serialize_i64, I128(i128) => S::serialize_i128,
+            Usize(u64) => serialize_usize, Isize(i64) => serialize_isize
+        );
+        match (format, val) {
+            (Format::Unit, Value::Unit) => self.ser.serialize_unit(),
+            (Format::Bool, &Value::Bool(v)) => self.ser.serialize_bool(v),
+            // not through `f64`, which could change a NaN's payload
+            (Format::F32, &Value::F32(v)) => self.ser.serialize_f32(v),
+            (Format::F32, _) => match val.float() {
+                Some(v) => self.ser.serialize_f32(v as f32),
+                None => Err(mismatch(format, val)),
+            },
+            (Format::F64, _) => match val.float() {
+                Some(v) => self.ser.serialize_f64(v),
+                None => Err(mismatch(format, val)),
+            },
+            (Format::Char, &Value::Char(c)) => self.ser.serialize_char(c),
+            (Format::Char, Value::Str(s)) if s.chars().count() == 1 => {
+                self.ser.serialize_char(s.chars().next().unwrap())
+            }
+            (Format::Str, Value::Str(s)) => self.ser.serialize_str(s),
+            (Format::Str, &Value::Char(c)) => {
+                let mut buf = [0; 4];
+                self.ser.serialize_str(c.encode_utf8(&mut buf))
+            }
+            (Format::Bytes, Value::Bytes(bytes)) => self.ser.serialize_bytes(bytes),
+            (Format::Bytes, _) => {
+                let bytes = self.elements(&Format::U8, val)?;
+                let bytes: Vec<u8> = bytes
+                    .iter()
+                    .map(|b| match b.integer() {
+                        Some(Integer::Unsigned(v)) if v <= 0xFF => Ok(v as u8),
+                        _ => Err(mismatch(&Format::U8, b)),
+                    })
+                    .collect::<Result<_, Error>>()?;
+                self.ser.serialize_bytes(&bytes)
+            }
+            (Format::Option(inner), _) => match *val {
+                Value::Option(None) | Value::Unit => self.ser.serialize_none(),
+                Value::Option(Some(ref v)) => self.some(inner, v),
+                ref v => self.some(inner, v),
+            },
+            (Format::Seq(element), _) => {
+                let elements = self.elements(format, val)?;
+                self.ser.mark(Marker::Seq)?;
+                self.ser.write_len(elements.len())?;
+                elements.iter().try_for_each(|v| self.format(element, v))
+            }
+            (Format::Map { key, value }, Value::Map(entries)) => {
+                if O::Mode::CANONICAL {
+                    return Err(Error::new(ErrorKind::NonCanonicalMap));
+                }
+                self.ser.mark(Marker::Map)?;
+                self.ser.write_len(entries.len())?;
+                entries.iter().try_for_each(|(k, v)| {
+                    self.key(key, k)?;
+                    self.format(value, v)
+                })
+            }
+            (Format::Tuple(formats), _) => self.tuple(formats, val),
+            (&Format::Array { ref element, len }, _) => {
+                let elements = self.elements(format, val)?;
+                if elements.len() != len {
+                    return Err(custom(format_args!(
+                        "expected {} elements, found {}",
+                        len,
+                        elements.len()
+                    )));
+                }
+                self.ser.start_fixed(Marker::Seq, len)?;
+                elements.iter().try_for_each(|v| self.format(element, v))
+            }
+            (
+                &Format::Bounded {
+                    ref element,
+                    capacity,
+                },
+                _,
+            ) => self.bounded(format, element, capacity, val),
+            (Format::Named(name), _) => self.named(name, val),
+            _ => Err(mismatch(format, val)),
+        }
+    }
+
+    /// Encode a map key. JSON only has string keys, so `Value::to_json` writes any others as the
+    /// string of their JSON, which is read back here for keys which aren't strings themselves.
+    fn key(&mut self, format: &Format, key: &Value) -> Result<(), Error> {
+        #[cfg(feature = "std")]
+        {
+            if let Value::Str(ref s) = *key {
+                if !matches!(*format, Format::Str | Format::Char) {
+                    if let Ok(key) = Value::from_json(s) {
+                        return self.format(format, &key);
+                    }
+                }
+            }
+        }
+        self.format(format, key)
+    }
+
+    fn some(&mut self, inner: &Format, val: &Value) -> Result<(), Error> {
+        if O::Format::DESCRIBED {
+            self.ser.mark(Marker::Some)?;
+        } else {
+            self.ser.write_u8(1)?;
+        }
+        self.format(inner, val)
+    }
+
+    fn elements<'v>(&self, format: &Format, val: &'v Value) -> Result<Cow<'v, [Value]>, Error> {
+        val.elements().ok_or_else(|| mismatch(format, val))
+    }
+
+    fn tuple(&mut self, formats: &[Format], val: &Value) -> Result<(), Error> {
+        let elements = val
+            .elements()
+            .ok_or_else(|| mismatch(&Format::Tuple(formats.to_vec()), val))?;
+        if elements.len() != formats.len() {
+            return Err(custom(format_args!(
+                "expected {} elements, found {}",
+                formats.len(),
+                elements.len()
+            )));
+        }
+        self.ser.start_fixed(Marker::Seq, formats.len())?;
+        formats
+            .iter()
+            .zip(elements.iter())
+            .try_for_each(|(f, v)| self.format(f, v))
+    }
+
+    fn fields(&mut self, fields: &[Field], val: &Value) -> Result<(), Error> {
+        if !matches!(*val, Value::Struct(_) | Value::Map(_)) {
+            return Err(custom(format_args!(
+                "expected a struct, found {}",
+                val.kind()
+            )));
+        }
+        self.ser.start_fixed(Marker::Map, fields.len())?;
+        fields.iter().try_for_each(|f| {
+            let v = val
+                .field(&f.name)
+                .ok_or_else(|| custom(format_args!("missing field `{}`", f.name)))?;
+            if O::Format::DESCRIBED {
+                self.ser.serialize_str(&f.name)?;
+            }
+            self.format(&f.format, v)
+        })
+    }
+
+    fn bounded(
+        &mut self,
+        format: &Format,
+        element: &Format,
+        capacity: usize,
+        val: &Value,
+    ) -> Result<(), Error> {
+        let elements = self.elements(format, val)?;
+        let len = elements.len();
+        if len > capacity {
+            return Err(Error::new(ErrorKind::CapacityExceeded));
+        }
+        self.ser.start_fixed(Marker::Seq, len + 1)?;
+        match prefix_width(capacity) {
+            1 => self.ser.serialize_u8(len as u8)?,
+            2 => self.ser.serialize_u16(len as u16)?,
+            _ => self.ser.serialize_u32(len as u32)?,
+        }
+        elements.iter().try_for_each(|v| self.format(element, v))
+    }
+
+    fn named(&mut self, name: &str, val: &Value) -> Result<(), Error> {
+        match *container(self.schema, name)? {
+            Container::UnitStruct => match *val {
+                Value::Unit => self.ser.serialize_unit(),
+                _ => Err(custom(format_args!(
+                    "expected `{}`, found {}",
+                    name,
+                    val.kind()
+                ))),
+            },
+            Container::NewtypeStruct(ref inner) => self.format(inner, val),
+            Container::TupleStruct(ref formats) => self.tuple(formats, val),
+            Container::Struct(ref fields) => self.fields(fields, val),
+            Container::Enum(ref variants) => {
+                let (variant, contents) = val.variant().ok_or_else(|| {
+                    custom(format_args!("expected `{}`, found {}", name, val.kind()))
+                })?;
+                let index = variants
+                    .iter()
+                    .position(|v| v.name == variant)
+                    .ok_or_else(|| {
+                        custom(format_args!("`{}` has no variant `{}`", name, variant))
+                    })?;
+                self.ser.write_variant(name, index as u32, variant)?;
+                match variants[index].contents {
+                    VariantFormat::Unit => match *contents {
+                        Value::Unit => self.ser.serialize_unit(),
+                        ref v => Err(custom(format_args!(
+                            "expected no contents for `{}::{}`, found {}",
+                            name,
+                            variant,
+                            v.kind()
+                        ))),
+                    },
+                    VariantFormat::Newtype(ref inner) => self.format(inner, &contents),
+                    VariantFormat::Tuple(ref formats) => self.tuple(formats, &contents),
+                    VariantFormat::Struct(ref fields) => self.fields(fields, &contents),
+                }
+            }
+        }
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Value::Unit => serializer.serialize_unit(),
+            Value::Bool(v) => serializer.serialize_bool(v),
+            Value::U8(v) => serializer.serialize_u8(v),
+            Value::U16(v) => serializer.serialize_u16(v),
+            Value::U32(v) => serializer.serialize_u32(v),
+            Value::U64(v) => serializer.serialize_u64(v),
+            Value::U128(v) => serializer.serialize_u128(v),
+            Value::I8(v) => serializer.serialize_i8(v),
+            Value::I16(v) => serializer.serialize_i16(v),
+            Value::I32(v) => serializer.serialize_i32(v),
+            Value::I64(v) => serializer.serialize_i64(v),
+            Value::I128(v) => serializer.serialize_i128(v),
+            Value::F32(v) => serializer.serialize_f32(v),
+            Value::F64(v) => serializer.serialize_f64(v),
+            Value::Char(v) => serializer.serialize_char(v),
+            Value::Str(ref v) => serializer.serialize_str(v),
+            Value::Bytes(ref v) => serializer.serialize_bytes(v),
+            Value::Option(None) => serializer.serialize_none(),
+            Value::Option(Some(ref v)) => serializer.serialize_some(v),
+            Value::Seq(ref elements) => {
+                let mut seq = serializer.serialize_seq(Some(elements.len()))?;
+                for v in elements {
+                    seq.serialize_element(v)?;
+                }
+                seq.end()
+            }
+            Value::Map(ref entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (k, v) in entries {
+                    map.serialize_entry(k, v)?;
+                }
+                map.end()
+            }
+            Value::Struct(ref fields) => {
+                let mut map = serializer.serialize_map(Some(fields.len()))?;
+                for (k, v) in fields {
+                    map.serialize_entry(k, v)?;
+                }
+                map.end()
+            }
+            // externally tagged, as serde_json does it
+            Value::Variant(ref name, ref contents) => match **contents {
+                Value::Unit => serializer.serialize_str(name),
+                ref contents => {
+                    let mut map = serializer.serialize_map(Some(1))?;
+                    map.serialize_entry(name, contents)?;
+                    map.end()
+                }
+            },
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+macro_rules! visit_value {
+    ($($method:ident($ty:ty) => $name:ident),*) => {
+        $(
+            fn $method<E: de::Error>(self, v: $ty) -> Result<Value, E> {
+                Ok(Value::$name(v.into()))
+            }
+        )*
+    }
+}
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any value")
+    }
+
+    visit_value! {
+        visit_bool(bool) => Bool,
+        visit_u8(u8) => U8,
+        visit_u16(u16) => U16,
+        visit_u32(u32) => U32,
+        visit_u64(u64) => U64,
+        visit_u128(u128) => U128,
+        visit_i8(i8) => I8,
+        visit_i16(i16) => I16,
+        visit_i32(i32) => I32,
+        visit_i64(i64) => I64,
+        visit_i128(i128) => I128,
+        visit_f32(f32) => F32,
+        visit_f64(f64) => F64,
+        visit_char(char) => Char,
+        visit_str(&str) => Str,
+        visit_string(String) => Str,
+        visit_bytes(&[u8]) => Bytes,
+        visit_byte_buf(Vec<u8>) => Bytes
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Unit)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Option(None))
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(self, d: D) -> Result<Value, D::Error> {
+        Ok(Value::Option(Some(Box::new(Value::deserialize(d)?))))
+    }
+
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(self, d: D) -> Result<Value, D::Error> {
+        Value::deserialize(d)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut elements = Vec::new();
+        while let Some(v) = seq.next_element()? {
+            elements.push(v);
+        }
+        Ok(Value::Seq(elements))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut entries = Vec::new();
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(Value::Map(entries))
+    }
+}
+
+/// A value as `Value::to_json` writes it, which serde_json can't do for all of them by itself.
+#[cfg(feature = "std")]
+struct Json<'a>(&'a Value);
+
+#[cfg(feature = "std")]
+impl<'a> Serialize for Json<'a> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self.0 {
+            // serde_json would write `null`, which can't be told apart from `None`
+            Value::F32(v) if !v.is_finite() => serializer.collect_str(&v),
+            Value::F64(v) if !v.is_finite() => serializer.collect_str(&v),
+            Value::Option(Some(ref v)) => serializer.serialize_some(&Json(v)),
+            Value::Seq(ref elements) => serializer.collect_seq(elements.iter().map(Json)),
+            Value::Map(ref entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (k, v) in entries {
+                    match *k {
+                        Value::Str(ref k) => map.serialize_entry(k, &Json(v))?,
+                        ref k => {
+                            let k = serde_json::to_string(&Json(k)).map_err(ser::Error::custom)?;
+                            map.serialize_entry(&k, &Json(v))?
+                        }
+                    }
+                }
+                map.end()
+            }
+            Value::Struct(ref fields) => {
+                serializer.collect_map(fields.iter().map(|(k, v)| (k, Json(v))))
+            }
+            Value::Variant(ref name, ref contents) if **contents != Value::Unit => {
+                serializer.collect_map(Some((name, Json(contents))))
+            }
+            ref v => v.serialize(serializer),
+        }
+    }
+}
+
+/// How deeply arrays and objects can be nested in `Value::from_json`, so deep text can't overflow
+/// the stack.
+#[cfg(feature = "std")]
+const MAX_JSON_DEPTH: usize = 256;
+
+/// Read the JSON `raw` from within `text` as a value, `depth` arrays and objects into `text`.
+///
+/// serde_json has checked the syntax already, but would read integers too large for 64 bits as
+/// floats, so numbers are sized here from their text and only the rest is left to serde_json.
+#[cfg(feature = "std")]
+fn from_raw_json(text: &str, raw: &RawValue, depth: usize) -> Result<Value, Error> {
+    let json = raw.get();
+    let at = json.as_ptr() as usize - text.as_ptr() as usize;
+    let parse = |e: serde_json::Error| custom(format_args!("{}", e)).at(at);
+    match json.as_bytes()[0] {
+        b'[' | b'{' if depth == MAX_JSON_DEPTH => {
+            Err(custom(format_args!("nested too deeply")).at(at))
+        }
+        b'[' => {
+            let elements: Vec<&RawValue> = serde_json::from_str(json).map_err(parse)?;
+            let elements = elements
+                .into_iter()
+                .map(|v| from_raw_json(text, v, depth + 1));
+            Ok(Value::Seq(elements.collect::<Result<_, _>>()?))
+        }
+        b'{' => {
+            let RawEntries(entries) = serde_json::from_str(json).map_err(parse)?;
+            let entries = entries
+                .into_iter()
+                .map(|(k, v)| Ok((Value::Str(k), from_raw_json(text, v, depth + 1)?)));
+            Ok(Value::Map(entries.collect::<Result<_, Error>>()?))
+        }
+        b'-' | b'0'..=b'9' => Ok(if let Ok(v) = json.parse() {
+            Value::U64(v)
+        } else if let Ok(v) = json.parse() {
+            Value::I64(v)
+        } else if let Ok(v) = json.parse() {
+            Value::U128(v)
+        } else if let Ok(v) = json.parse() {
+            Value::I128(v)
+        } else {
+            Value::F64(
+                json.parse()
+                    .map_err(|_| custom(format_args!("invalid number")).at(at))?,
+            )
+        }),
+        _ => serde_json::from_str(json).map_err(parse),
+    }
+}
+
+/// The entries of a JSON object, with their values left unparsed.
+#[cfg(feature = "std")]
+struct RawEntries<'a>(Vec<(String, &'a RawValue)>);
+
+#[cfg(feature = "std")]
+impl<'de: 'a, 'a> Deserialize<'de> for RawEntries<'a> {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EntriesVisitor;
+
+        impl<'de> Visitor<'de> for EntriesVisitor {
+            type Value = RawEntries<'de>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<RawEntries<'de>, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(RawEntries(entries))
+            }
+        }
+
+        deserializer.deserialize_map(EntriesVisitor)
+    }
+}
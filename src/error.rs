@@ -0,0 +1,360 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+use core::fmt::{self, Display};
+use core::iter::Rev;
+use core::slice::Iter;
+
+#[cfg(feature = "alloc")]
+use alloc::string::{String, ToString};
+#[cfg(feature = "std")]
+use std::io;
+
+use limits::Limit;
+
+/// The number of path segments an `Error` keeps track of.
+pub const MAX_DEPTH: usize = 4;
+
+/// What went wrong.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The buffer ended, or ran out of room, before the value was complete.
+    EndOfStream,
+    /// A value's encoding was invalid in a way not covered by a more specific kind.
+    InvalidRepresentation,
+    /// A `bool` was encoded as something other than 0 or 1.
+    InvalidBool(u8),
+    /// An `Option` tag was something other than 0 or 1.
+    InvalidOptionTag(u8),
+    /// A `char` wasn't valid UTF-8.
+    InvalidChar,
+    /// A string wasn't valid UTF-8.
+    InvalidUtf8,
+    /// An enum discriminant didn't match any variant.
+    UnknownVariant { index: u32 },
+    /// A sequence or map wasn't read to the end, in strict mode.
+    MoreElements,
+    /// A sequence or map was serialized with a length other than its number of elements.
+    LengthMismatch { len: usize, count: usize },
+    /// Bytes were left over after the value, in strict mode.
+    TrailingBytes,
+    /// A float was a NaN other than the canonical one, in canonical mode.
+    NonCanonicalNan,
+    /// A map was serialized or deserialized in canonical mode, where maps aren't allowed.
+    NonCanonicalMap,
+    /// A type marker was unknown, or didn't fit the type being decoded, in the self-describing
+    /// format.
+    InvalidMarker(u8),
+    /// The value went past one of the `Limits` it was decoded with.
+    LimitExceeded(Limit),
+    /// An enum had more variants than the configured discriminant width can number.
+    TooManyVariants,
+    /// An integer or length didn't fit the width it's encoded with.
+    Overflow,
+    /// A bounded collection's length was more than its capacity.
+    CapacityExceeded,
+    /// The type needs something the format can't do, like `deserialize_any` when values don't
+    /// describe themselves.
+    NotSupported,
+    /// Reading or writing failed, other than by reaching the end of the stream.
+    #[cfg(feature = "std")]
+    Io(io::ErrorKind),
+    /// An error the application reported with a fixed message.
+    ApplicationError(&'static str),
+    /// An error reported by a `Serialize` or `Deserialize` impl; the message needs `alloc`.
+    #[cfg(not(feature = "alloc"))]
+    Custom,
+    /// An error reported by a `Serialize` or `Deserialize` impl.
+    #[cfg(feature = "alloc")]
+    Custom(String),
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ErrorKind::EndOfStream => f.write_str("end of stream reached but more data was needed"),
+            ErrorKind::InvalidRepresentation => f.write_str("invalid representation for a value"),
+            ErrorKind::InvalidBool(b) => write!(f, "invalid bool {:#04x}", b),
+            ErrorKind::InvalidOptionTag(b) => write!(f, "invalid option tag {:#04x}", b),
+            ErrorKind::InvalidChar => f.write_str("invalid UTF-8 in char"),
+            ErrorKind::InvalidUtf8 => f.write_str("invalid UTF-8 in string"),
+            ErrorKind::UnknownVariant { index } => write!(f, "unknown enum variant {}", index),
+            ErrorKind::MoreElements => {
+                f.write_str("there are more elements of the sequence remaining")
+            }
+            ErrorKind::LengthMismatch { len, count } => {
+                write!(f, "sequence of length {} had {} elements", len, count)
+            }
+            ErrorKind::TrailingBytes => f.write_str("trailing bytes after the value"),
+            ErrorKind::NonCanonicalNan => f.write_str("non-canonical NaN"),
+            ErrorKind::NonCanonicalMap => f.write_str("maps have no canonical encoding"),
+            ErrorKind::InvalidMarker(b) => write!(f, "invalid type marker {:#04x}", b),
+            ErrorKind::LimitExceeded(limit) => write!(f, "{} limit exceeded", limit),
+            ErrorKind::TooManyVariants => {
+                f.write_str("too many enum variants for the configured discriminant width")
+            }
+            ErrorKind::Overflow => f.write_str("integer too large for the configured width"),
+            ErrorKind::CapacityExceeded => f.write_str("length exceeds the collection's capacity"),
+            ErrorKind::NotSupported => f.write_str("feature not supported"),
+            #[cfg(feature = "std")]
+            ErrorKind::Io(kind) => write!(f, "I/O error: {}", io::Error::from(kind)),
+            ErrorKind::ApplicationError(s) => write!(f, "application error: {}", s),
+            #[cfg(not(feature = "alloc"))]
+            ErrorKind::Custom => f.write_str("some custom error that couldn't be reported"),
+            #[cfg(feature = "alloc")]
+            ErrorKind::Custom(ref s) => f.write_str(s),
+        }
+    }
+}
+
+/// One step on the way from the outermost value to where an error happened.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// A named struct field.
+    Field(&'static str),
+    /// An element of a tuple, sequence or map.
+    Index(usize),
+    /// The contents of an enum variant.
+    Variant(&'static str),
+}
+
+impl Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Segment::Field(name) => write!(f, ".{}", name),
+            Segment::Index(idx) => write!(f, "[{}]", idx),
+            Segment::Variant(name) => write!(f, "::{}", name),
+        }
+    }
+}
+
+/// How a `Segment` is stored: names point into the `fields` or `variants` slices serde hands the
+/// deserializer, so each one takes two words instead of three. This keeps `Error` small enough to
+/// return by value everywhere.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Crumb {
+    Field(&'static &'static str),
+    Index(usize),
+    Variant(&'static &'static str),
+}
+
+impl Crumb {
+    fn segment(&self) -> Segment {
+        match *self {
+            Crumb::Field(name) => Segment::Field(name),
+            Crumb::Index(idx) => Segment::Index(idx),
+            Crumb::Variant(name) => Segment::Variant(name),
+        }
+    }
+}
+
+/// Where in a value an error happened, as a fixed-depth breadcrumb trail.
+///
+/// Only the innermost `MAX_DEPTH` segments are kept; `is_truncated` says whether any outer ones
+/// were dropped.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Path {
+    // innermost first, since that's the order they're added while unwinding
+    crumbs: [Crumb; MAX_DEPTH],
+    len: u8,
+    truncated: bool,
+}
+
+impl Path {
+    fn new() -> Path {
+        Path {
+            crumbs: [Crumb::Index(0); MAX_DEPTH],
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    /// The segments, from the outermost value inwards.
+    pub fn iter(&self) -> Segments<'_> {
+        Segments {
+            inner: self.crumbs[..self.len()].iter().rev(),
+        }
+    }
+
+    /// The number of segments kept.
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Whether the error happened at the top level, outside any field, element or variant.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the path was deeper than `MAX_DEPTH`, losing its outermost segments.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn push_outer(&mut self, crumb: Crumb) {
+        if self.len() < MAX_DEPTH {
+            self.crumbs[self.len()] = crumb;
+            self.len += 1;
+        } else {
+            self.truncated = true;
+        }
+    }
+}
+
+impl fmt::Debug for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.truncated {
+            f.write_str("...")?;
+        }
+        for segment in self.iter() {
+            Display::fmt(&segment, f)?;
+        }
+        Ok(())
+    }
+}
+
+/// An iterator over the segments of a `Path`, from the outermost value inwards.
+pub struct Segments<'a> {
+    inner: Rev<Iter<'a, Crumb>>,
+}
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = Segment;
+
+    fn next(&mut self) -> Option<Segment> {
+        self.inner.next().map(Crumb::segment)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for Segments<'a> {
+    fn next_back(&mut self) -> Option<Segment> {
+        self.inner.next_back().map(Crumb::segment)
+    }
+}
+
+impl<'a> ExactSizeIterator for Segments<'a> {}
+
+/// An error, along with where it happened.
+///
+/// When deserializing, the offset is that of the value which couldn't be decoded, and the path
+/// leads to it from the outermost value. When serializing, the offset is the number of bytes
+/// written before the error and the path is empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    kind: ErrorKind,
+    offset: Option<usize>,
+    path: Path,
+}
+
+impl Error {
+    /// An error of `kind`, with no offset or path yet.
+    pub fn new(kind: ErrorKind) -> Error {
+        Error {
+            kind,
+            offset: None,
+            path: Path::new(),
+        }
+    }
+
+    /// What went wrong.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// The byte offset into the buffer, if known.
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    /// Where in the value it went wrong.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record `offset`, unless an inner value already did.
+    pub(crate) fn at(mut self, offset: usize) -> Error {
+        if self.offset.is_none() {
+            self.offset = Some(offset);
+        }
+        self
+    }
+
+    /// Record that the error happened inside `crumb`.
+    pub(crate) fn within(mut self, crumb: Crumb) -> Error {
+        self.path.push_outer(crumb);
+        self
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Error {
+        Error::new(kind)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.kind, f)?;
+        if let Some(offset) = self.offset {
+            write!(f, " at byte {}", offset)?;
+        }
+        if !self.path.is_empty() {
+            write!(f, " in {}", self.path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "alloc"))]
+impl serde::de::Error for Error {
+    fn custom<T: Display>(_msg: T) -> Error {
+        Error::new(ErrorKind::Custom)
+    }
+}
+
+#[cfg(not(feature = "alloc"))]
+impl serde::ser::Error for Error {
+    fn custom<T: Display>(_msg: T) -> Error {
+        Error::new(ErrorKind::Custom)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl serde::de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Error {
+        Error::new(ErrorKind::Custom(msg.to_string()))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl serde::ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Error {
+        Error::new(ErrorKind::Custom(msg.to_string()))
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        match e.kind() {
+            io::ErrorKind::UnexpectedEof => Error::new(ErrorKind::EndOfStream),
+            kind => Error::new(ErrorKind::Io(kind)),
+        }
+    }
+}
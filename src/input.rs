@@ -0,0 +1,341 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+//! Sources a `Deserializer` can read from.
+//!
+//! Besides a contiguous byte slice, values can be read from a buffer split into several segments
+//! (such as a ring buffer which has wrapped) with `SegmentedInput`, from an array of words with
+//! `WordInput`, from any iterator of bytes with `IterInput`, and with `std` from an `io::Read`
+//! with `IoInput`. Implement `Input` to read from anywhere else.
+//!
+//! Only inputs which can hand out slices of themselves can decode borrowed `&str` and `&[u8]`
+//! values. The others need the `alloc` feature to decode owned strings and byte buffers, which
+//! are then copied out.
+
+use core::mem::{size_of, size_of_val};
+
+#[cfg(feature = "std")]
+use std::io;
+
+use {Error, ErrorKind, DEBUG_PANICS};
+
+/// Somewhere a `Deserializer` can read bytes from. `'de` is the lifetime of the data borrowed
+/// values can point into.
+pub trait Input<'de> {
+    /// Fill `buf` with the next bytes, or fail with `ErrorKind::EndOfStream` if there aren't
+    /// enough of them.
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error>;
+
+    /// Take the next `len` bytes without copying them, if they're contiguous in memory. Returns
+    /// `None`, without consuming anything, if they aren't; the deserializer then falls back on
+    /// `read_exact`. Never borrows by default.
+    #[inline]
+    fn read_borrowed(&mut self, len: usize) -> Result<Option<&'de [u8]>, Error> {
+        let _ = len;
+        Ok(None)
+    }
+
+    /// The number of bytes read so far.
+    fn position(&self) -> usize;
+}
+
+#[cold]
+fn end_of_stream() -> Error {
+    if DEBUG_PANICS {
+        panic!("ran out of space deserializing value; fix your buffer size");
+    }
+    ErrorKind::EndOfStream.into()
+}
+
+/// A contiguous byte buffer being read from the start.
+#[derive(Debug, Clone)]
+pub struct SliceInput<'a> {
+    buf: &'a [u8],
+    idx: usize,
+}
+
+impl<'a> SliceInput<'a> {
+    /// Start reading at the beginning of `buf`.
+    pub fn new(buf: &'a [u8]) -> SliceInput<'a> {
+        SliceInput { buf, idx: 0 }
+    }
+
+    /// The number of bytes left to read.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.idx
+    }
+
+    /// Get back the underlying buffer.
+    pub fn into_inner(self) -> &'a [u8] {
+        self.buf
+    }
+
+    #[inline]
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if len > self.remaining() {
+            return Err(end_of_stream());
+        }
+        let buf: &'a [u8] = self.buf;
+        let slice = &buf[self.idx..self.idx + len];
+        self.idx += len;
+        Ok(slice)
+    }
+}
+
+impl<'a> Input<'a> for SliceInput<'a> {
+    #[inline]
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        buf.copy_from_slice(self.take(buf.len())?);
+        Ok(())
+    }
+
+    #[inline]
+    fn read_borrowed(&mut self, len: usize) -> Result<Option<&'a [u8]>, Error> {
+        self.take(len).map(Some)
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.idx
+    }
+}
+
+/// A buffer split into several segments, read as if they were one after another.
+///
+/// A ring buffer which has wrapped around is two segments: the part up to the end of its storage
+/// and the part from the start. Borrowed values which lie entirely within one segment are handed
+/// out without copying.
+#[derive(Debug, Clone)]
+pub struct SegmentedInput<'s, 'a: 's> {
+    segments: &'s [&'a [u8]],
+    // the segment being read and the offset into it
+    seg: usize,
+    idx: usize,
+    pos: usize,
+}
+
+impl<'s, 'a: 's> SegmentedInput<'s, 'a> {
+    /// Start reading at the beginning of the first segment.
+    pub fn new(segments: &'s [&'a [u8]]) -> SegmentedInput<'s, 'a> {
+        SegmentedInput {
+            segments,
+            seg: 0,
+            idx: 0,
+            pos: 0,
+        }
+    }
+
+    /// The number of bytes left to read.
+    pub fn remaining(&self) -> usize {
+        let rest: usize = self.segments[self.seg.min(self.segments.len())..]
+            .iter()
+            .map(|s| s.len())
+            .sum();
+        rest - self.idx
+    }
+
+    /// Move on past exhausted segments.
+    #[inline]
+    fn skip_empty(&mut self) {
+        while self.seg < self.segments.len() && self.idx == self.segments[self.seg].len() {
+            self.seg += 1;
+            self.idx = 0;
+        }
+    }
+}
+
+impl<'s, 'a: 's> Input<'a> for SegmentedInput<'s, 'a> {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        if buf.len() > self.remaining() {
+            return Err(end_of_stream());
+        }
+        let mut done = 0;
+        while done < buf.len() {
+            self.skip_empty();
+            let seg = self.segments[self.seg];
+            let n = (seg.len() - self.idx).min(buf.len() - done);
+            buf[done..done + n].copy_from_slice(&seg[self.idx..self.idx + n]);
+            self.idx += n;
+            done += n;
+        }
+        self.pos += done;
+        Ok(())
+    }
+
+    fn read_borrowed(&mut self, len: usize) -> Result<Option<&'a [u8]>, Error> {
+        if len > self.remaining() {
+            return Err(end_of_stream());
+        }
+        self.skip_empty();
+        if len == 0 {
+            return Ok(Some(&[]));
+        }
+        let seg: &'a [u8] = self.segments[self.seg];
+        if seg.len() - self.idx < len {
+            return Ok(None);
+        }
+        let slice = &seg[self.idx..self.idx + len];
+        self.idx += len;
+        self.pos += len;
+        Ok(Some(slice))
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.pos
+    }
+}
+
+/// An array of machine words being read from the start, say message registers.
+///
+/// Each word is read as the bytes it has in memory, so this reads back what `output::WordOutput`
+/// wrote.
+#[derive(Debug, Clone)]
+pub struct WordInput<'a> {
+    words: &'a [usize],
+    idx: usize,
+}
+
+impl<'a> WordInput<'a> {
+    /// Start reading at the beginning of the first word.
+    pub fn new(words: &'a [usize]) -> WordInput<'a> {
+        WordInput { words, idx: 0 }
+    }
+
+    /// The number of bytes left to read.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        size_of_val(self.words) - self.idx
+    }
+}
+
+impl<'de, 'a> Input<'de> for WordInput<'a> {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        if buf.len() > self.remaining() {
+            return Err(end_of_stream());
+        }
+        let mut done = 0;
+        while done < buf.len() {
+            let (word, at) = (self.idx / size_of::<usize>(), self.idx % size_of::<usize>());
+            let n = (size_of::<usize>() - at).min(buf.len() - done);
+            buf[done..done + n].copy_from_slice(&self.words[word].to_ne_bytes()[at..at + n]);
+            self.idx += n;
+            done += n;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.idx
+    }
+}
+
+/// Bytes taken one at a time from an iterator, say one fed by a UART interrupt handler.
+#[derive(Debug, Clone)]
+pub struct IterInput<I> {
+    iter: I,
+    pos: usize,
+}
+
+impl<I: Iterator<Item = u8>> IterInput<I> {
+    /// Start reading from the next byte of `iter`.
+    pub fn new<T: IntoIterator<IntoIter = I, Item = u8>>(iter: T) -> IterInput<I> {
+        IterInput {
+            iter: iter.into_iter(),
+            pos: 0,
+        }
+    }
+
+    /// Get back the iterator, positioned after the last byte read.
+    pub fn into_inner(self) -> I {
+        self.iter
+    }
+}
+
+impl<'de, I: Iterator<Item = u8>> Input<'de> for IterInput<I> {
+    #[inline]
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        for b in buf.iter_mut() {
+            *b = self.iter.next().ok_or_else(end_of_stream)?;
+            self.pos += 1;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.pos
+    }
+}
+
+/// Bytes read from an `io::Read`, exactly as many as each value needs.
+///
+/// Reads are small, so wrap unbuffered readers in an `io::BufReader`. The end of the stream is
+/// reported as `ErrorKind::EndOfStream`, and other I/O errors as `ErrorKind::Io`, at the offset
+/// the failed read started from. The position still counts any bytes that read did take.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct IoInput<R> {
+    reader: R,
+    pos: usize,
+}
+
+#[cfg(feature = "std")]
+impl<R: io::Read> IoInput<R> {
+    /// Start reading from `reader`, counting offsets from where it is now.
+    pub fn new(reader: R) -> IoInput<R> {
+        IoInput { reader, pos: 0 }
+    }
+
+    /// Get back the reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de, R: io::Read> Input<'de> for IoInput<R> {
+    fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<(), Error> {
+        // not `io::Read::read_exact`, which doesn't say how much it read before failing, so that
+        // the position counts every byte taken from the reader, like `IterInput`'s
+        while !buf.is_empty() {
+            match self.reader.read(buf) {
+                Ok(0) => return Err(end_of_stream()),
+                Ok(n) => {
+                    self.pos += n;
+                    buf = &mut buf[n..];
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.pos
+    }
+}
+
+impl<'de, R: Input<'de> + ?Sized> Input<'de> for &mut R {
+    #[inline]
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        (**self).read_exact(buf)
+    }
+
+    #[inline]
+    fn read_borrowed(&mut self, len: usize) -> Result<Option<&'de [u8]>, Error> {
+        (**self).read_borrowed(len)
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        (**self).position()
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+use core::fmt::{self, Display};
+
+/// Bounds on how much work decoding a single value may do, for buffers from untrusted peers.
+///
+/// Lengths read off the wire can be anything up to the configured length width, and recursive
+/// types such as `Box`ed enums can nest as deep as the buffer allows. Each call to
+/// `Deserializer::deserialize` checks the value it decodes against these limits, failing with
+/// `ErrorKind::LimitExceeded` as soon as one is crossed. Nothing is limited by default.
+///
+/// ```rust
+/// use ssmarshal::{Deserializer, ErrorKind, Limit, Limits};
+///
+/// let limits = Limits::new().with_max_len(4);
+/// let buf = [5, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4, 5];
+/// let mut de = Deserializer::new(&buf).with_limits(limits);
+/// let err = de.deserialize::<Vec<u8>>().unwrap_err();
+/// assert_eq!(err.kind(), &ErrorKind::LimitExceeded(Limit::Len));
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Limits {
+    max_len: usize,
+    max_depth: usize,
+    max_elements: usize,
+}
+
+impl Limits {
+    /// No limits at all.
+    pub const fn new() -> Limits {
+        Limits {
+            max_len: usize::MAX,
+            max_depth: usize::MAX,
+            max_elements: usize::MAX,
+        }
+    }
+
+    /// Reject sequences, maps, strings and byte slices whose length prefix is over `max`.
+    pub const fn with_max_len(self, max: usize) -> Limits {
+        Limits {
+            max_len: max,
+            ..self
+        }
+    }
+
+    /// Reject values nested more than `max` levels deep. Every sequence, tuple, struct, map,
+    /// enum variant, `Some` and newtype struct counts as a level.
+    pub const fn with_max_depth(self, max: usize) -> Limits {
+        Limits {
+            max_depth: max,
+            ..self
+        }
+    }
+
+    /// Reject values whose sequences, maps, strings and byte slices have more than `max` elements
+    /// between them, going by their length prefixes. A string's elements are its bytes.
+    pub const fn with_max_elements(self, max: usize) -> Limits {
+        Limits {
+            max_elements: max,
+            ..self
+        }
+    }
+
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    pub fn max_elements(&self) -> usize {
+        self.max_elements
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits::new()
+    }
+}
+
+/// Which of the `Limits` was exceeded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Limit {
+    /// The length of a single sequence or map.
+    Len,
+    /// The nesting depth.
+    Depth,
+    /// The total number of sequence and map elements.
+    Elements,
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Limit::Len => "length",
+            Limit::Depth => "depth",
+            Limit::Elements => "element",
+        })
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+use core::fmt;
+use core::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use {Deserializer, Error, ErrorKind, MaxSize, Serializer};
+
+/// The encoding of a `T`, in a buffer of `N` bytes which keeps track of how much of it is used.
+///
+/// `N` must be at least `T::MAX_SIZE`, which is checked at compile time, so encoding never runs
+/// out of room. The buffer is aligned like `A`, `()` by default, for transports which need it.
+///
+/// ```rust
+/// # extern crate serde;
+/// # #[macro_use] extern crate serde_derive;
+/// # extern crate ssmarshal;
+/// use ssmarshal::{Marshaled, MaxSize};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize, MaxSize)]
+/// enum Msg {
+///     Ping,
+///     Move(i16, i16),
+/// }
+///
+/// # fn main() {
+/// let frame = Marshaled::<Msg, { Msg::MAX_SIZE }>::encode(&Msg::Move(1, -1)).unwrap();
+/// assert_eq!(frame.as_bytes(), &[1, 1, 0, 0xFF, 0xFF]);
+/// assert_eq!(frame.decode().unwrap(), Msg::Move(1, -1));
+/// # }
+/// ```
+#[repr(C)]
+pub struct Marshaled<T, const N: usize, A = ()> {
+    _align: [A; 0],
+    buf: [u8; N],
+    len: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: MaxSize, const N: usize, A> Marshaled<T, N, A> {
+    const FITS: () = assert!(N >= T::MAX_SIZE, "buffer smaller than the type's MAX_SIZE");
+
+    /// Encode `val`.
+    pub fn encode(val: &T) -> Result<Marshaled<T, N, A>, Error>
+    where
+        T: Serialize,
+    {
+        // evaluating this fails the build if `N` is too small
+        let () = Self::FITS;
+        let mut buf = [0; N];
+        let len = Serializer::new(&mut buf).serialize(val)?;
+        Ok(Marshaled {
+            _align: [],
+            buf,
+            len,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Take bytes received from elsewhere as the encoding of a `T`. Fails with
+    /// `ErrorKind::CapacityExceeded` if there are more than `N` of them; they are only checked
+    /// when decoded.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Marshaled<T, N, A>, Error> {
+        let () = Self::FITS;
+        if bytes.len() > N {
+            return Err(ErrorKind::CapacityExceeded.into());
+        }
+        let mut buf = [0; N];
+        buf[..bytes.len()].copy_from_slice(bytes);
+        Ok(Marshaled {
+            _align: [],
+            buf,
+            len: bytes.len(),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Decode the value, which must take up all of the bytes.
+    pub fn decode(&self) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let mut deserializer = Deserializer::new(self.as_bytes());
+        let val = deserializer.deserialize()?;
+        deserializer.end()?;
+        Ok(val)
+    }
+}
+
+impl<T, const N: usize, A> Marshaled<T, N, A> {
+    /// The encoded bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    /// The number of bytes used.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the value encoded to no bytes at all, as `()` and empty structs do.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T, const N: usize, A> AsRef<[u8]> for Marshaled<T, N, A> {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl<T, const N: usize, A> Clone for Marshaled<T, N, A> {
+    fn clone(&self) -> Marshaled<T, N, A> {
+        Marshaled {
+            _align: [],
+            buf: self.buf,
+            len: self.len,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, const N: usize, A> PartialEq for Marshaled<T, N, A> {
+    fn eq(&self, other: &Marshaled<T, N, A>) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl<T, const N: usize, A> Eq for Marshaled<T, N, A> {}
+
+impl<T, const N: usize, A> fmt::Debug for Marshaled<T, N, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Marshaled").field(&self.as_bytes()).finish()
+    }
+}
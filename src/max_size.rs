@@ -0,0 +1,117 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+use core::num::{
+    NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize, NonZeroU128,
+    NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize,
+};
+
+/// Types with a compile-time upper bound on their encoded size.
+///
+/// `MAX_SIZE` is the most bytes `serialize` can ever write for any value of the type, so a buffer
+/// of `[u8; T::MAX_SIZE]` is always large enough. Unlike `size_of::<T>()` this is also correct for
+/// non-`#[repr(C)]` enums and for `Option`s of niche-optimized types.
+///
+/// With the `derive` feature this can be derived for structs and enums whose fields all implement
+/// `MaxSize`. Types that ssmarshal can't encode, like `String` or slices, don't implement it, so
+/// deriving it for a type containing them is a compile error.
+pub trait MaxSize {
+    /// The maximum number of bytes a value of this type serializes to.
+    const MAX_SIZE: usize;
+}
+
+macro_rules! max_size {
+    ($($t:ty => $size:expr),* $(,)*) => {
+        $(impl MaxSize for $t {
+            const MAX_SIZE: usize = $size;
+        })*
+    }
+}
+
+max_size! {
+    () => 0,
+    bool => 1,
+    u8 => 1,
+    u16 => 2,
+    u32 => 4,
+    u64 => 8,
+    u128 => 16,
+    usize => 8,
+    i8 => 1,
+    i16 => 2,
+    i32 => 4,
+    i64 => 8,
+    i128 => 16,
+    isize => 8,
+    f32 => 4,
+    f64 => 8,
+    char => 4,
+    NonZeroU8 => 1,
+    NonZeroU16 => 2,
+    NonZeroU32 => 4,
+    NonZeroU64 => 8,
+    NonZeroU128 => 16,
+    NonZeroUsize => 8,
+    NonZeroI8 => 1,
+    NonZeroI16 => 2,
+    NonZeroI32 => 4,
+    NonZeroI64 => 8,
+    NonZeroI128 => 16,
+    NonZeroIsize => 8,
+}
+
+impl<T: ?Sized> MaxSize for PhantomData<T> {
+    const MAX_SIZE: usize = 0;
+}
+
+impl<T: MaxSize + ?Sized> MaxSize for &T {
+    const MAX_SIZE: usize = T::MAX_SIZE;
+}
+
+impl<T: MaxSize + ?Sized> MaxSize for &mut T {
+    const MAX_SIZE: usize = T::MAX_SIZE;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: MaxSize + ?Sized> MaxSize for Box<T> {
+    const MAX_SIZE: usize = T::MAX_SIZE;
+}
+
+impl<T: MaxSize> MaxSize for Option<T> {
+    const MAX_SIZE: usize = 1 + T::MAX_SIZE;
+}
+
+impl<T: MaxSize, const N: usize> MaxSize for [T; N] {
+    const MAX_SIZE: usize = N * T::MAX_SIZE;
+}
+
+macro_rules! max_size_tuple {
+    ($($name:ident)+) => {
+        impl<$($name: MaxSize),+> MaxSize for ($($name,)+) {
+            const MAX_SIZE: usize = 0 $(+ $name::MAX_SIZE)+;
+        }
+    }
+}
+
+max_size_tuple!(T0);
+max_size_tuple!(T0 T1);
+max_size_tuple!(T0 T1 T2);
+max_size_tuple!(T0 T1 T2 T3);
+max_size_tuple!(T0 T1 T2 T3 T4);
+max_size_tuple!(T0 T1 T2 T3 T4 T5);
+max_size_tuple!(T0 T1 T2 T3 T4 T5 T6);
+max_size_tuple!(T0 T1 T2 T3 T4 T5 T6 T7);
+max_size_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8);
+max_size_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8 T9);
+max_size_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10);
+max_size_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11);
+max_size_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12);
+max_size_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12 T13);
+max_size_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12 T13 T14);
+max_size_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12 T13 T14 T15);
@@ -0,0 +1,149 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+//! Encode `Option`s of `NonZero` integers without a tag byte.
+//!
+//! An `Option` normally takes a tag byte before its contents, so `Option<NonZeroU32>` is encoded
+//! in 5 bytes although it only takes 4 in memory, breaking the size invariant. Wrapping it in
+//! `Niche`, or marking the field with `#[serde(with = "ssmarshal::niche")]`, encodes it like the
+//! plain integer instead, with `None` as zero. Every encoding decodes to a value, so nothing new
+//! can be rejected. Other serde formats see the same integer.
+//!
+//! Prefer the wrapper when deriving `MaxSize`: the derive can't see `with` attributes, so it still
+//! counts a tag byte for such fields.
+//!
+//! ```rust
+//! # extern crate serde;
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate ssmarshal;
+//! use std::num::NonZeroU32;
+//! use ssmarshal::niche::Niche;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Handles {
+//!     parent: Niche<NonZeroU32>,
+//!     #[serde(with = "ssmarshal::niche")]
+//!     child: Option<NonZeroU32>,
+//! }
+//!
+//! # fn main() {
+//! let handles = Handles { parent: Niche(None), child: NonZeroU32::new(7) };
+//! let mut buf = [0xFFu8; 8];
+//! assert_eq!(ssmarshal::serialize(&mut buf, &handles).unwrap(), 8);
+//! assert_eq!(buf, [0, 0, 0, 0, 7, 0, 0, 0]);
+//! # }
+//! ```
+
+use core::num::{
+    NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize, NonZeroU128,
+    NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize,
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use MaxSize;
+
+/// Integers with a zero niche: the `NonZero` types.
+pub trait NonZero: Sized + Copy {
+    #[doc(hidden)]
+    fn serialize_niche<S: Serializer>(val: Option<Self>, serializer: S) -> Result<S::Ok, S::Error>;
+    #[doc(hidden)]
+    fn deserialize_niche<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Self>, D::Error>;
+}
+
+/// Serialize an `Option` of a `NonZero` integer as the integer, or zero for `None`.
+pub fn serialize<T: NonZero, S: Serializer>(
+    val: &Option<T>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    T::serialize_niche(*val, serializer)
+}
+
+/// Deserialize an `Option` of a `NonZero` integer encoded as the integer, or zero for `None`.
+pub fn deserialize<'de, T: NonZero, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<T>, D::Error> {
+    T::deserialize_niche(deserializer)
+}
+
+/// An `Option` of a `NonZero` integer, encoded as the integer with `None` as zero.
+///
+/// This takes as many bytes on the wire as it does in memory, unlike a bare `Option`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct Niche<T>(pub Option<T>);
+
+impl<T> Default for Niche<T> {
+    fn default() -> Niche<T> {
+        Niche(None)
+    }
+}
+
+impl<T> From<Option<T>> for Niche<T> {
+    fn from(val: Option<T>) -> Niche<T> {
+        Niche(val)
+    }
+}
+
+impl<T> From<Niche<T>> for Option<T> {
+    fn from(val: Niche<T>) -> Option<T> {
+        val.0
+    }
+}
+
+impl<T: NonZero> Serialize for Niche<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        T::serialize_niche(self.0, serializer)
+    }
+}
+
+impl<'de, T: NonZero> Deserialize<'de> for Niche<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Niche<T>, D::Error> {
+        T::deserialize_niche(deserializer).map(Niche)
+    }
+}
+
+impl<T: NonZero + MaxSize> MaxSize for Niche<T> {
+    const MAX_SIZE: usize = T::MAX_SIZE;
+}
+
+macro_rules! non_zero {
+    ($($ty:ident($int:ident)),* $(,)*) => {
+        $(
+            impl NonZero for $ty {
+                fn serialize_niche<S: Serializer>(
+                    val: Option<$ty>,
+                    serializer: S,
+                ) -> Result<S::Ok, S::Error> {
+                    val.map_or(0, $ty::get).serialize(serializer)
+                }
+
+                fn deserialize_niche<'de, D: Deserializer<'de>>(
+                    deserializer: D,
+                ) -> Result<Option<$ty>, D::Error> {
+                    $int::deserialize(deserializer).map($ty::new)
+                }
+            }
+        )*
+    }
+}
+
+non_zero! {
+    NonZeroU8(u8),
+    NonZeroU16(u16),
+    NonZeroU32(u32),
+    NonZeroU64(u64),
+    NonZeroU128(u128),
+    NonZeroUsize(usize),
+    NonZeroI8(i8),
+    NonZeroI16(i16),
+    NonZeroI32(i32),
+    NonZeroI64(i64),
+    NonZeroI128(i128),
+    NonZeroIsize(isize),
+}
@@ -0,0 +1,597 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+//! Sinks a `Serializer` can write to.
+//!
+//! Besides byte slices, vectors and the bounded vectors of the `heapless` and `arrayvec` crates,
+//! values can be written across several buffers with `SegmentedOutput`, into an array of words
+//! with `WordOutput`, into uninitialized memory with `UninitOutput`, fed to a `Hasher` with
+//! `HashOutput`, or seen on their way through with `Inspect`, say to compute a CRC. Implement
+//! `Output` to write anywhere else.
+//!
+//! Only outputs which keep what was written can `patch` it, which is needed to encode sequences
+//! whose length isn't known until they've been written.
+
+use core::hash::Hasher;
+use core::mem::{size_of, size_of_val, MaybeUninit};
+
+#[cfg(feature = "std")]
+use std::io;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use {ns, Error, ErrorKind, DEBUG_PANICS};
+
+/// Somewhere a `Serializer` can write bytes to.
+pub trait Output {
+    /// Append `bytes`, or fail with `ErrorKind::EndOfStream` if there isn't room for all of them.
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error>;
+
+    /// Get ready for `additional` more bytes, which are about to be written. This is only a
+    /// hint, so it does nothing by default; growable outputs can use it to allocate once.
+    #[inline]
+    fn reserve(&mut self, additional: usize) -> Result<(), Error> {
+        let _ = additional;
+        Ok(())
+    }
+
+    /// Overwrite bytes already written, starting `pos` bytes in as counted by `position`. This
+    /// fills in the length of a sequence once its elements have been counted. Outputs which
+    /// pass bytes on as they're written can't go back, and fail with `ErrorKind::NotSupported`,
+    /// which is the default.
+    #[inline]
+    fn patch(&mut self, pos: usize, bytes: &[u8]) -> Result<(), Error> {
+        let _ = (pos, bytes);
+        ns()
+    }
+
+    /// The number of bytes written so far.
+    fn position(&self) -> usize;
+}
+
+#[cold]
+fn out_of_space() -> Error {
+    if DEBUG_PANICS {
+        panic!("ran out of space serializing value; fix your buffer size");
+    }
+    ErrorKind::EndOfStream.into()
+}
+
+/// Overwrite part of the `written` bytes of an in-memory output.
+#[inline]
+fn patch_written(written: &mut [u8], pos: usize, bytes: &[u8]) -> Result<(), Error> {
+    match pos
+        .checked_add(bytes.len())
+        .and_then(|end| written.get_mut(pos..end))
+    {
+        Some(dst) => {
+            dst.copy_from_slice(bytes);
+            Ok(())
+        }
+        None => Err(out_of_space()),
+    }
+}
+
+/// A fixed-size byte buffer being written from the start.
+pub struct SliceOutput<'a> {
+    buf: &'a mut [u8],
+    idx: usize,
+}
+
+impl<'a> SliceOutput<'a> {
+    /// Start writing at the beginning of `buf`.
+    pub fn new(buf: &'a mut [u8]) -> SliceOutput<'a> {
+        SliceOutput { buf, idx: 0 }
+    }
+
+    /// The number of bytes left in the buffer.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.idx
+    }
+
+    /// Get back the underlying buffer.
+    pub fn into_inner(self) -> &'a mut [u8] {
+        self.buf
+    }
+}
+
+impl<'a> Output for SliceOutput<'a> {
+    #[inline]
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        if let Some(end) = self.idx.checked_add(bytes.len()) {
+            if end <= self.buf.len() {
+                self.buf[self.idx..end].copy_from_slice(bytes);
+                self.idx = end;
+                return Ok(());
+            }
+        }
+        Err(out_of_space())
+    }
+
+    #[inline]
+    fn patch(&mut self, pos: usize, bytes: &[u8]) -> Result<(), Error> {
+        patch_written(&mut self.buf[..self.idx], pos, bytes)
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.idx
+    }
+}
+
+/// A buffer split into several segments, written as if they were one after another.
+///
+/// Values are split across segment boundaries wherever they fall, so the bytes are the same as
+/// they would be in one contiguous buffer.
+pub struct SegmentedOutput<'s, 'a: 's> {
+    segments: &'s mut [&'a mut [u8]],
+    // the segment being written and the offset into it
+    seg: usize,
+    idx: usize,
+    pos: usize,
+}
+
+impl<'s, 'a: 's> SegmentedOutput<'s, 'a> {
+    /// Start writing at the beginning of the first segment.
+    pub fn new(segments: &'s mut [&'a mut [u8]]) -> SegmentedOutput<'s, 'a> {
+        SegmentedOutput {
+            segments,
+            seg: 0,
+            idx: 0,
+            pos: 0,
+        }
+    }
+
+    /// The number of bytes left in the segments.
+    pub fn remaining(&self) -> usize {
+        let rest: usize = self.segments[self.seg.min(self.segments.len())..]
+            .iter()
+            .map(|s| s.len())
+            .sum();
+        rest - self.idx
+    }
+}
+
+impl<'s, 'a: 's> Output for SegmentedOutput<'s, 'a> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        if bytes.len() > self.remaining() {
+            return Err(out_of_space());
+        }
+        let mut done = 0;
+        while done < bytes.len() {
+            while self.idx == self.segments[self.seg].len() {
+                self.seg += 1;
+                self.idx = 0;
+            }
+            let seg = &mut self.segments[self.seg];
+            let n = (seg.len() - self.idx).min(bytes.len() - done);
+            seg[self.idx..self.idx + n].copy_from_slice(&bytes[done..done + n]);
+            self.idx += n;
+            done += n;
+        }
+        self.pos += done;
+        Ok(())
+    }
+
+    fn patch(&mut self, pos: usize, bytes: &[u8]) -> Result<(), Error> {
+        match pos.checked_add(bytes.len()) {
+            Some(end) if end <= self.pos => {}
+            _ => return Err(out_of_space()),
+        }
+        // `start` is the position of the beginning of `seg`
+        let mut start = 0;
+        let mut done = 0;
+        for seg in self.segments.iter_mut() {
+            if done == bytes.len() {
+                break;
+            }
+            if pos + done < start + seg.len() {
+                let at = pos + done - start;
+                let n = (seg.len() - at).min(bytes.len() - done);
+                seg[at..at + n].copy_from_slice(&bytes[done..done + n]);
+                done += n;
+            }
+            start += seg.len();
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.pos
+    }
+}
+
+/// An array of machine words being written from the start, say message registers.
+///
+/// Each word holds the bytes it has in memory, so the words hold the same bytes a byte buffer
+/// would, and can be read back with `input::WordInput`. Bytes in the last word past the end of
+/// the value are left as they were.
+#[derive(Debug)]
+pub struct WordOutput<'a> {
+    words: &'a mut [usize],
+    idx: usize,
+}
+
+impl<'a> WordOutput<'a> {
+    /// Start writing at the beginning of the first word.
+    pub fn new(words: &'a mut [usize]) -> WordOutput<'a> {
+        WordOutput { words, idx: 0 }
+    }
+
+    /// The number of bytes left in the words.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        size_of_val(self.words) - self.idx
+    }
+
+    /// The number of words written to so far, including a partly written last one.
+    #[inline]
+    pub fn words_used(&self) -> usize {
+        self.idx.div_ceil(size_of::<usize>())
+    }
+
+    /// Copy `bytes` into the words, starting `pos` bytes in.
+    fn write_at(&mut self, pos: usize, bytes: &[u8]) {
+        let mut done = 0;
+        while done < bytes.len() {
+            let (word, at) = (
+                (pos + done) / size_of::<usize>(),
+                (pos + done) % size_of::<usize>(),
+            );
+            let n = (size_of::<usize>() - at).min(bytes.len() - done);
+            let mut buf = self.words[word].to_ne_bytes();
+            buf[at..at + n].copy_from_slice(&bytes[done..done + n]);
+            self.words[word] = usize::from_ne_bytes(buf);
+            done += n;
+        }
+    }
+}
+
+impl<'a> Output for WordOutput<'a> {
+    #[inline]
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        if bytes.len() > self.remaining() {
+            return Err(out_of_space());
+        }
+        self.write_at(self.idx, bytes);
+        self.idx += bytes.len();
+        Ok(())
+    }
+
+    #[inline]
+    fn patch(&mut self, pos: usize, bytes: &[u8]) -> Result<(), Error> {
+        match pos.checked_add(bytes.len()) {
+            Some(end) if end <= self.idx => {
+                self.write_at(pos, bytes);
+                Ok(())
+            }
+            _ => Err(out_of_space()),
+        }
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.idx
+    }
+}
+
+/// Counts the bytes written to it without storing them, to find out how large an encoding is.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct SizeCounter {
+    len: usize,
+}
+
+impl SizeCounter {
+    /// Start counting from zero.
+    pub fn new() -> SizeCounter {
+        SizeCounter { len: 0 }
+    }
+}
+
+impl Output for SizeCounter {
+    #[inline]
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        match self.len.checked_add(bytes.len()) {
+            Some(len) => {
+                self.len = len;
+                Ok(())
+            }
+            None => Err(ErrorKind::Overflow.into()),
+        }
+    }
+
+    /// The bytes were already counted, so there's nothing to do.
+    #[inline]
+    fn patch(&mut self, pos: usize, bytes: &[u8]) -> Result<(), Error> {
+        let _ = (pos, bytes);
+        Ok(())
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.len
+    }
+}
+
+/// Appends to the vector, growing it as needed.
+#[cfg(feature = "alloc")]
+impl Output for Vec<u8> {
+    #[inline]
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) -> Result<(), Error> {
+        Vec::reserve(self, additional);
+        Ok(())
+    }
+
+    #[inline]
+    fn patch(&mut self, pos: usize, bytes: &[u8]) -> Result<(), Error> {
+        patch_written(self, pos, bytes)
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<W: Output + ?Sized> Output for &mut W {
+    #[inline]
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        (**self).write_bytes(bytes)
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) -> Result<(), Error> {
+        (**self).reserve(additional)
+    }
+
+    #[inline]
+    fn patch(&mut self, pos: usize, bytes: &[u8]) -> Result<(), Error> {
+        (**self).patch(pos, bytes)
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        (**self).position()
+    }
+}
+
+/// Appends to the vector, failing once it's full.
+#[cfg(feature = "heapless")]
+impl<const N: usize> Output for heapless::Vec<u8, N> {
+    #[inline]
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.extend_from_slice(bytes).map_err(|_| out_of_space())
+    }
+
+    #[inline]
+    fn patch(&mut self, pos: usize, bytes: &[u8]) -> Result<(), Error> {
+        patch_written(self, pos, bytes)
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Appends to the vector, failing once it's full.
+#[cfg(feature = "arrayvec")]
+impl<const N: usize> Output for arrayvec::ArrayVec<u8, N> {
+    #[inline]
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.try_extend_from_slice(bytes)
+            .map_err(|_| out_of_space())
+    }
+
+    #[inline]
+    fn patch(&mut self, pos: usize, bytes: &[u8]) -> Result<(), Error> {
+        patch_written(self, pos, bytes)
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Bytes written to an `io::Write` as they're produced.
+///
+/// Writes are small, so wrap unbuffered writers in an `io::BufWriter`. I/O errors are reported as
+/// `ErrorKind::Io`.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct IoOutput<W> {
+    writer: W,
+    pos: usize,
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write> IoOutput<W> {
+    /// Start writing to `writer`.
+    pub fn new(writer: W) -> IoOutput<W> {
+        IoOutput { writer, pos: 0 }
+    }
+
+    /// Get back the writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write> Output for IoOutput<W> {
+    #[inline]
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.writer.write_all(bytes)?;
+        self.pos += bytes.len();
+        Ok(())
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.pos
+    }
+}
+
+/// A fixed-size buffer of uninitialized memory, such as a DMA buffer, being written from the
+/// start.
+pub struct UninitOutput<'a> {
+    buf: &'a mut [MaybeUninit<u8>],
+    idx: usize,
+}
+
+impl<'a> UninitOutput<'a> {
+    /// Start writing at the beginning of `buf`.
+    pub fn new(buf: &'a mut [MaybeUninit<u8>]) -> UninitOutput<'a> {
+        UninitOutput { buf, idx: 0 }
+    }
+
+    /// The number of bytes left in the buffer.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.idx
+    }
+
+    /// Get back the part of the buffer written so far, now initialized.
+    pub fn into_init(self) -> &'a mut [u8] {
+        let init = &mut self.buf[..self.idx];
+        // every byte before `idx` has been written, and `MaybeUninit<u8>` has the layout of `u8`
+        unsafe { core::slice::from_raw_parts_mut(init.as_mut_ptr() as *mut u8, init.len()) }
+    }
+}
+
+impl<'a> Output for UninitOutput<'a> {
+    #[inline]
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        if let Some(end) = self.idx.checked_add(bytes.len()) {
+            if end <= self.buf.len() {
+                for (dst, &src) in self.buf[self.idx..end].iter_mut().zip(bytes) {
+                    *dst = MaybeUninit::new(src);
+                }
+                self.idx = end;
+                return Ok(());
+            }
+        }
+        Err(out_of_space())
+    }
+
+    #[inline]
+    fn patch(&mut self, pos: usize, bytes: &[u8]) -> Result<(), Error> {
+        match pos.checked_add(bytes.len()) {
+            Some(end) if end <= self.idx => {
+                for (dst, &src) in self.buf[pos..end].iter_mut().zip(bytes) {
+                    *dst = MaybeUninit::new(src);
+                }
+                Ok(())
+            }
+            _ => Err(out_of_space()),
+        }
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.idx
+    }
+}
+
+/// Feeds everything written to a `Hasher`, to hash a value's encoding without storing it.
+#[derive(Debug, Clone, Default)]
+pub struct HashOutput<H> {
+    hasher: H,
+    len: usize,
+}
+
+impl<H: Hasher> HashOutput<H> {
+    /// Start feeding bytes to `hasher`.
+    pub fn new(hasher: H) -> HashOutput<H> {
+        HashOutput { hasher, len: 0 }
+    }
+
+    /// The hash of everything written so far.
+    pub fn finish(&self) -> u64 {
+        self.hasher.finish()
+    }
+
+    /// Get back the hasher.
+    pub fn into_inner(self) -> H {
+        self.hasher
+    }
+}
+
+impl<H: Hasher> Output for HashOutput<H> {
+    #[inline]
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.hasher.write(bytes);
+        self.len = self.len.saturating_add(bytes.len());
+        Ok(())
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.len
+    }
+}
+
+/// Writes to another output, passing each chunk of bytes to a closure once it's been written.
+///
+/// ```rust
+/// use ssmarshal::output::Inspect;
+/// use ssmarshal::{DefaultOptions, Serializer, SliceOutput};
+///
+/// let mut buf = [0u8; 8];
+/// let mut sum = 0u32;
+/// let out = Inspect::new(SliceOutput::new(&mut buf), |bytes: &[u8]| {
+///     sum = bytes.iter().fold(sum, |sum, &b| sum.wrapping_add(b as u32));
+/// });
+/// let mut serializer = Serializer::from_output(out, DefaultOptions::new());
+/// serializer.serialize(&(1u16, 2u32)).unwrap();
+/// drop(serializer);
+/// assert_eq!(sum, 3);
+/// ```
+pub struct Inspect<W, F> {
+    out: W,
+    f: F,
+}
+
+impl<W: Output, F: FnMut(&[u8])> Inspect<W, F> {
+    /// Write to `out`, passing `f` each run of bytes once `out` has taken it.
+    pub fn new(out: W, f: F) -> Inspect<W, F> {
+        Inspect { out, f }
+    }
+
+    /// Get back the output being written to.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Output, F: FnMut(&[u8])> Output for Inspect<W, F> {
+    #[inline]
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.out.write_bytes(bytes)?;
+        (self.f)(bytes);
+        Ok(())
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) -> Result<(), Error> {
+        self.out.reserve(additional)
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.out.position()
+    }
+}
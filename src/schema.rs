@@ -0,0 +1,971 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+//! Describing the encoding of a type at runtime.
+//!
+//! The bare format can't be decoded without knowing the exact type. `trace` walks a type's
+//! `Deserialize` impl with a deserializer which records what it's asked for instead of reading
+//! any bytes, and returns a `Schema` of the type: its primitives, structs with their field names,
+//! tuples, arrays, options and enums with their variants, along with the encoding options. A
+//! `Schema` is itself serializable, so it can be written next to a capture with ssmarshal, or as
+//! JSON with `std`, and used to find the maximum encoded size or decode values dynamically.
+//!
+//! ```rust
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate ssmarshal;
+//! use ssmarshal::schema::{self, Container, Format};
+//!
+//! #[derive(Deserialize)]
+//! struct Point {
+//!     x: i16,
+//!     y: Option<u8>,
+//! }
+//!
+//! # fn main() {
+//! let schema = schema::trace::<Point>().unwrap();
+//! assert_eq!(schema.root, Format::Named("Point".into()));
+//! match schema.types["Point"] {
+//!     Container::Struct(ref fields) => {
+//!         assert_eq!(fields[1].format, Format::Option(Box::new(Format::U8)))
+//!     }
+//!     _ => unreachable!(),
+//! }
+//! assert_eq!(schema.max_size(), Some(4));
+//! # }
+//! ```
+//!
+//! Tracing relies on the type's `Deserialize` impl accepting the small sample values it's handed:
+//! `1` for integers, empty strings and a single element for each sequence. Types which only
+//! deserialize through `deserialize_any`, such as those using `#[serde(flatten)]`, can't be
+//! traced. Structs and enums are identified by name, so two types with the same name in different
+//! modules are recorded as one. A tuple whose elements all have the same format is recorded as an
+//! array, since the two are encoded the same way.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::mem;
+
+use serde::de::{
+    self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess,
+    Visitor,
+};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use serde_json;
+
+#[cfg(any(feature = "heapless", feature = "arrayvec"))]
+use bounded::BOUNDED_TOKEN;
+use config::{prefix_width, ByteOrder, DefaultOptions, Format as _, Mode as _, Options, Width};
+use {word, Error, ErrorKind};
+
+/// A description of how values of a type are encoded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Schema {
+    /// The options the type is encoded with.
+    pub encoding: Encoding,
+    /// The format of the traced type itself.
+    pub root: Format,
+    /// The structs and enums reachable from the root, by name.
+    pub types: BTreeMap<String, Container>,
+}
+
+/// The encoding options a `Schema` was traced with, as set by a `Config`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Encoding {
+    /// Whether integers and floats are big-endian.
+    pub big_endian: bool,
+    /// The width in bytes of `usize` and `isize` words.
+    pub usize_width: u8,
+    /// The width in bytes of sequence lengths.
+    pub len_width: u8,
+    /// The width in bytes of enum discriminants.
+    pub tag_width: u8,
+    /// Whether values are preceded by type markers, in the `SelfDescribing` format.
+    pub self_describing: bool,
+    /// Whether only the one encoding of each value is accepted, in the `Canonical` mode.
+    #[serde(default)]
+    pub canonical: bool,
+}
+
+impl Encoding {
+    /// The encoding set by the options `O`.
+    pub fn of<O: Options>() -> Encoding {
+        Encoding {
+            big_endian: O::Endian::BIG_ENDIAN,
+            usize_width: O::Usize::BYTES as u8,
+            len_width: O::Len::BYTES as u8,
+            tag_width: O::Tag::BYTES as u8,
+            self_describing: O::Format::DESCRIBED,
+            canonical: O::Mode::CANONICAL,
+        }
+    }
+}
+
+impl Default for Encoding {
+    fn default() -> Encoding {
+        Encoding::of::<DefaultOptions>()
+    }
+}
+
+/// The format of a value.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Format {
+    /// Nothing at all.
+    #[default]
+    Unit,
+    /// One byte, 0 or 1.
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    /// The bits of the float, as a `u32`.
+    F32,
+    /// The bits of the float, as a `u64`.
+    F64,
+    /// One to four bytes of UTF-8.
+    Char,
+    /// A length, then that many bytes of UTF-8.
+    Str,
+    /// A length, then that many bytes.
+    Bytes,
+    /// A `usize` marked with `ssmarshal::word`, in the configured word width.
+    Usize,
+    /// An `isize` marked with `ssmarshal::word`, in the configured word width.
+    Isize,
+    /// A tag byte, 0 for `None` or 1 for `Some` followed by the value.
+    Option(Box<Format>),
+    /// A length, then that many elements.
+    Seq(Box<Format>),
+    /// A length, then that many keys each followed by its value.
+    Map {
+        key: Box<Format>,
+        value: Box<Format>,
+    },
+    /// The elements, in order.
+    Tuple(Vec<Format>),
+    /// `len` elements, in order.
+    Array {
+        element: Box<Format>,
+        len: usize,
+    },
+    /// A length prefix sized from the capacity, as in `ssmarshal::bounded`, then the elements.
+    Bounded {
+        element: Box<Format>,
+        capacity: usize,
+    },
+    /// The struct or enum of that name in `Schema::types`.
+    Named(String),
+}
+
+/// The format of a named struct or enum.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Container {
+    /// A struct without fields, which takes no space.
+    UnitStruct,
+    /// A struct wrapping one value, encoded as that value.
+    NewtypeStruct(Format),
+    /// A struct with unnamed fields, encoded as a tuple of them.
+    TupleStruct(Vec<Format>),
+    /// A struct with named fields, encoded as its fields in order.
+    Struct(Vec<Field>),
+    /// The discriminant, then the contents of that variant. Variants are listed by discriminant.
+    Enum(Vec<Variant>),
+}
+
+/// A named field of a struct or struct variant.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Field {
+    pub name: String,
+    pub format: Format,
+}
+
+/// A variant of an enum.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Variant {
+    pub name: String,
+    pub contents: VariantFormat,
+}
+
+/// What follows the discriminant of an enum variant.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VariantFormat {
+    Unit,
+    Newtype(Format),
+    Tuple(Vec<Format>),
+    Struct(Vec<Field>),
+}
+
+impl Schema {
+    /// The largest number of bytes a value of the type encodes to, or `None` if there's no bound
+    /// because the type contains strings, sequences, maps or recursion.
+    ///
+    /// For the default options this is never more than `MaxSize`, and less where fields use `with`
+    /// modules like `ssmarshal::niche` or `ssmarshal::bounded`, which the derive can't see.
+    pub fn max_size(&self) -> Option<usize> {
+        Sizer {
+            schema: self,
+            visiting: Vec::new(),
+        }
+        .format(&self.root)
+    }
+
+    /// Write the schema as JSON.
+    #[cfg(feature = "std")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("schemas are always valid JSON")
+    }
+
+    /// Read a schema written by [`to_json`](#method.to_json).
+    #[cfg(feature = "std")]
+    pub fn from_json(text: &str) -> Result<Schema, Error> {
+        serde_json::from_str(text).map_err(|e| json_error(text, &e))
+    }
+}
+
+/// Trace the schema of `T` with the default options.
+pub fn trace<T: Deserialize<'static>>() -> Result<Schema, Error> {
+    trace_with::<T, _>(DefaultOptions::new())
+}
+
+/// Trace the schema of `T` as encoded with the options `opts`.
+pub fn trace_with<T: Deserialize<'static>, O: Options>(_opts: O) -> Result<Schema, Error> {
+    let mut tracer = Tracer::default();
+    // each pass explores another variant of each enum, until every variant has been seen
+    loop {
+        let before = tracer.progress();
+        tracer.start_pass();
+        match T::deserialize(&mut tracer) {
+            Ok(_) => {
+                if let Some(name) = tracer.incomplete() {
+                    if tracer.progress() == before {
+                        return Err(custom(format_args!(
+                            "couldn't reach every variant of `{}`",
+                            name
+                        )));
+                    }
+                    continue;
+                }
+                let root = mem::replace(&mut tracer.format, Format::Unit);
+                return Ok(tracer.finish(root, Encoding::of::<O>()));
+            }
+            Err(e) => {
+                if tracer.progress() == before {
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn custom(msg: core::fmt::Arguments) -> Error {
+    Error::new(ErrorKind::Custom(msg.to_string()))
+}
+
+/// A serde_json error as one of ours, at the byte offset into `text` it reports.
+#[cfg(feature = "std")]
+pub(crate) fn json_error(text: &str, err: &serde_json::Error) -> Error {
+    let msg = err.to_string();
+    if err.line() == 0 {
+        return custom(format_args!("{}", msg));
+    }
+    let suffix = format!(" at line {} column {}", err.line(), err.column());
+    let line = text
+        .split('\n')
+        .take(err.line() - 1)
+        .map(|l| l.len() + 1)
+        .sum::<usize>();
+    let msg = msg.strip_suffix(&suffix[..]).unwrap_or(&msg);
+    custom(format_args!("{}", msg)).at(line + err.column().saturating_sub(1))
+}
+
+/// How deep named types can nest while tracing, as a backstop against runaway recursion.
+const MAX_NESTING: usize = 256;
+
+#[derive(Default)]
+struct EnumState {
+    variants: Vec<Option<VariantFormat>>,
+    names: &'static [&'static str],
+    /// Variants whose tracing failed, to be retried after the others.
+    deferred: Vec<bool>,
+}
+
+impl EnumState {
+    fn known(&self) -> usize {
+        self.variants.iter().filter(|v| v.is_some()).count()
+    }
+}
+
+/// A deserializer which records the format of everything it's asked for.
+///
+/// The first time a named type is reached it's traced in full: options are `Some`, and sequences
+/// and maps have one element. When a type is reached again inside itself, it's traced minimally
+/// instead, with options `None`, sequences and maps empty and enums using variants which have
+/// already been traced, so that the recursion ends.
+#[derive(Default)]
+struct Tracer {
+    types: BTreeMap<&'static str, Container>,
+    enums: BTreeMap<&'static str, EnumState>,
+    /// The named types being traced in full.
+    stack: Vec<&'static str>,
+    /// The named types being traced minimally, with the variant chosen for enums.
+    minimal: Vec<(&'static str, Option<u32>)>,
+    /// Whether the current value is being traced minimally.
+    cut: bool,
+    /// Whether an enum variant has been deferred in this pass.
+    deferred: bool,
+    /// The format of the value traced last.
+    format: Format,
+    variant: Option<VariantFormat>,
+}
+
+impl Tracer {
+    /// How much has been learned, which must grow with each pass for tracing to finish.
+    fn progress(&self) -> (usize, usize, usize) {
+        let deferred = self
+            .enums
+            .values()
+            .map(|e| e.deferred.iter().filter(|&&d| d).count())
+            .sum();
+        (
+            self.types.len(),
+            self.enums.values().map(EnumState::known).sum(),
+            deferred,
+        )
+    }
+
+    fn start_pass(&mut self) {
+        self.stack.clear();
+        self.minimal.clear();
+        self.cut = false;
+        self.deferred = false;
+    }
+
+    fn incomplete(&self) -> Option<&'static str> {
+        self.enums
+            .iter()
+            .find(|(_, e)| e.variants.iter().any(Option::is_none))
+            .map(|(name, _)| *name)
+    }
+
+    fn finish(self, root: Format, encoding: Encoding) -> Schema {
+        let mut types: BTreeMap<String, Container> = self
+            .types
+            .into_iter()
+            .map(|(name, c)| (name.to_string(), c))
+            .collect();
+        for (name, state) in self.enums {
+            let variants = state
+                .names
+                .iter()
+                .zip(state.variants)
+                .map(|(name, contents)| Variant {
+                    name: name.to_string(),
+                    contents: contents.expect("every variant was traced"),
+                })
+                .collect();
+            types.insert(name.to_string(), Container::Enum(variants));
+        }
+        Schema {
+            encoding,
+            root,
+            types,
+        }
+    }
+
+    fn take(&mut self) -> Format {
+        mem::replace(&mut self.format, Format::Unit)
+    }
+
+    /// Trace a named struct with `f`, in full if it hasn't been seen yet and minimally if it's
+    /// being traced already, recording its format unless it's traced minimally.
+    fn named<T, F>(&mut self, name: &'static str, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Tracer) -> Result<(T, Container), Error>,
+    {
+        let cut = self.cut;
+        let recursive = self.stack.contains(&name);
+        if recursive {
+            if self.minimal.iter().any(|&(n, _)| n == name) {
+                return Err(custom(format_args!("`{}` contains itself", name)));
+            }
+            self.minimal.push((name, None));
+            self.cut = true;
+        } else if !self.types.contains_key(name) {
+            // a type seen for the first time is traced in full, even inside a minimal one
+            self.cut = false;
+        }
+        if self.stack.len() + self.minimal.len() > MAX_NESTING {
+            return Err(Error::new(ErrorKind::LimitExceeded(::Limit::Depth)));
+        }
+        if !recursive {
+            self.stack.push(name);
+        }
+        let res = f(self);
+        if recursive {
+            self.minimal.pop();
+        } else {
+            self.stack.pop();
+        }
+        let traced_in_full = !self.cut;
+        self.cut = cut;
+        let (val, container) = res?;
+        if traced_in_full {
+            self.types.insert(name, container);
+        }
+        self.format = Format::Named(name.to_string());
+        Ok(val)
+    }
+
+    /// Pick the variant of enum `name` to trace, and whether to trace its contents minimally.
+    fn pick_variant(
+        &mut self,
+        name: &'static str,
+        variants: &'static [&'static str],
+    ) -> Result<(u32, bool), Error> {
+        let state = self.enums.entry(name).or_insert_with(|| EnumState {
+            variants: variants.iter().map(|_| None).collect(),
+            names: variants,
+            deferred: variants.iter().map(|_| false).collect(),
+        });
+        let recursive = self.stack.contains(&name);
+        if !recursive && (!self.cut || state.known() == 0) {
+            let unknown = |deferred: bool| {
+                (0..state.variants.len())
+                    .find(|&i| state.variants[i].is_none() && state.deferred[i] == deferred)
+            };
+            if let Some(i) = unknown(false).or_else(|| unknown(true)) {
+                return Ok((i as u32, false));
+            }
+            if !self.cut {
+                if let Some(i) = state.variants.iter().position(Option::is_some) {
+                    return Ok((i as u32, false));
+                }
+            }
+        }
+        // use a variant which is known to end, and isn't already being used further out
+        let minimal = &self.minimal;
+        (0..state.variants.len())
+            .find(|&i| state.variants[i].is_some() && !minimal.contains(&(name, Some(i as u32))))
+            .map(|i| (i as u32, true))
+            .ok_or_else(|| custom(format_args!("no variant of `{}` ends its recursion", name)))
+    }
+
+    fn seq<'de, V: Visitor<'de>>(
+        &mut self,
+        len: usize,
+        visitor: V,
+    ) -> Result<(V::Value, Vec<Format>), Error> {
+        let mut access = Elements {
+            tracer: self,
+            remaining: len,
+            formats: Vec::with_capacity(len),
+        };
+        let val = visitor.visit_seq(&mut access)?;
+        Ok((val, access.formats))
+    }
+}
+
+/// Collapse a tuple of identical elements into an array.
+fn tuple(formats: Vec<Format>) -> Format {
+    if formats.len() >= 2 && formats.iter().all(|f| *f == formats[0]) {
+        let len = formats.len();
+        let element = formats.into_iter().next().map(Box::new).unwrap();
+        return Format::Array { element, len };
+    }
+    Format::Tuple(formats)
+}
+
+fn fields(names: &'static [&'static str], formats: Vec<Format>) -> Vec<Field> {
+    names
+        .iter()
+        .zip(formats)
+        .map(|(name, format)| Field {
+            name: name.to_string(),
+            format,
+        })
+        .collect()
+}
+
+macro_rules! trace_primitive {
+    ($($method:ident => $visit:ident($val:expr), $format:ident;)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                let val = visitor.$visit::<Error>($val)?;
+                self.format = Format::$format;
+                Ok(val)
+            }
+        )*
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Tracer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(Error::new(ErrorKind::NotSupported))
+    }
+
+    trace_primitive! {
+        deserialize_bool => visit_bool(true), Bool;
+        deserialize_u8 => visit_u8(1), U8;
+        deserialize_u16 => visit_u16(1), U16;
+        deserialize_u32 => visit_u32(1), U32;
+        deserialize_u64 => visit_u64(1), U64;
+        deserialize_u128 => visit_u128(1), U128;
+        deserialize_i8 => visit_i8(1), I8;
+        deserialize_i16 => visit_i16(1), I16;
+        deserialize_i32 => visit_i32(1), I32;
+        deserialize_i64 => visit_i64(1), I64;
+        deserialize_i128 => visit_i128(1), I128;
+        deserialize_f32 => visit_f32(1.0), F32;
+        deserialize_f64 => visit_f64(1.0), F64;
+        deserialize_char => visit_char('a'), Char;
+        deserialize_str => visit_borrowed_str(""), Str;
+        deserialize_string => visit_borrowed_str(""), Str;
+        deserialize_bytes => visit_borrowed_bytes(b""), Bytes;
+        deserialize_byte_buf => visit_borrowed_bytes(b""), Bytes;
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let val = visitor.visit_unit::<Error>()?;
+        self.format = Format::Unit;
+        Ok(val)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.cut {
+            let val = visitor.visit_none::<Error>()?;
+            self.format = Format::Option(Box::default());
+            return Ok(val);
+        }
+        let val = visitor.visit_some(&mut *self)?;
+        self.format = Format::Option(Box::new(self.take()));
+        Ok(val)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.named(name, |_| {
+            Ok((visitor.visit_unit::<Error>()?, Container::UnitStruct))
+        })
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if name == word::USIZE_TOKEN {
+            let val =
+                visitor.visit_newtype_struct(IntoDeserializer::<Error>::into_deserializer(1u64))?;
+            self.format = Format::Usize;
+            return Ok(val);
+        }
+        if name == word::ISIZE_TOKEN {
+            let val =
+                visitor.visit_newtype_struct(IntoDeserializer::<Error>::into_deserializer(1i64))?;
+            self.format = Format::Isize;
+            return Ok(val);
+        }
+        self.named(name, |tracer| {
+            let val = visitor.visit_newtype_struct(&mut *tracer)?;
+            Ok((val, Container::NewtypeStruct(tracer.take())))
+        })
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let len = if self.cut { 0 } else { 1 };
+        let (val, mut formats) = self.seq(len, visitor)?;
+        self.format = Format::Seq(Box::new(formats.pop().unwrap_or_default()));
+        Ok(val)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        let (val, formats) = self.seq(len, visitor)?;
+        self.format = tuple(formats);
+        Ok(val)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        #[cfg(any(feature = "heapless", feature = "arrayvec"))]
+        {
+            if name == BOUNDED_TOKEN {
+                let capacity = len.saturating_sub(1);
+                let count = if self.cut { 0 } else { capacity.min(1) };
+                let mut access = Bounded {
+                    prefix: Some(count as u64),
+                    elements: Elements {
+                        tracer: self,
+                        remaining: count,
+                        formats: Vec::with_capacity(count),
+                    },
+                };
+                let val = visitor.visit_seq(&mut access)?;
+                let element = access.elements.formats.pop().unwrap_or_default();
+                self.format = Format::Bounded {
+                    element: Box::new(element),
+                    capacity,
+                };
+                return Ok(val);
+            }
+        }
+        self.named(name, |tracer| {
+            let (val, formats) = tracer.seq(len, visitor)?;
+            Ok((val, Container::TupleStruct(formats)))
+        })
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let mut access = Entries {
+            remaining: !self.cut,
+            tracer: self,
+            key: Format::Unit,
+            value: Format::Unit,
+        };
+        let val = visitor.visit_map(&mut access)?;
+        let (key, value) = (access.key, access.value);
+        self.format = Format::Map {
+            key: Box::new(key),
+            value: Box::new(value),
+        };
+        Ok(val)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        names: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.named(name, |tracer| {
+            let (val, formats) = tracer.seq(names.len(), visitor)?;
+            Ok((val, Container::Struct(fields(names, formats))))
+        })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let (index, minimal) = self.pick_variant(name, variants)?;
+        let cut = self.cut;
+        if minimal {
+            self.minimal.push((name, Some(index)));
+            self.cut = true;
+        } else {
+            self.stack.push(name);
+            self.cut = false;
+        }
+        let res = visitor.visit_enum(Variants {
+            tracer: &mut *self,
+            index,
+        });
+        if minimal {
+            self.minimal.pop();
+        } else {
+            self.stack.pop();
+        }
+        self.cut = cut;
+        let state = self.enums.get_mut(name).expect("enum was registered");
+        match res {
+            Ok(val) => {
+                let contents = self.variant.take();
+                if !minimal {
+                    state.variants[index as usize] = contents;
+                }
+                self.format = Format::Named(name.to_string());
+                Ok(val)
+            }
+            Err(e) => {
+                if !minimal && !self.deferred && state.variants[index as usize].is_none() {
+                    state.deferred[index as usize] = true;
+                    self.deferred = true;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(Error::new(ErrorKind::NotSupported))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// The elements of a tuple, struct or sequence.
+struct Elements<'a> {
+    tracer: &'a mut Tracer,
+    remaining: usize,
+    formats: Vec<Format>,
+}
+
+impl<'de, 'a, 'b> SeqAccess<'de> for &'b mut Elements<'a> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        let val = seed.deserialize(&mut *self.tracer)?;
+        self.formats.push(self.tracer.take());
+        Ok(Some(val))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+/// The prefix and elements of a bounded collection.
+#[cfg(any(feature = "heapless", feature = "arrayvec"))]
+struct Bounded<'a> {
+    prefix: Option<u64>,
+    elements: Elements<'a>,
+}
+
+#[cfg(any(feature = "heapless", feature = "arrayvec"))]
+impl<'de, 'a, 'b> SeqAccess<'de> for &'b mut Bounded<'a> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        match self.prefix.take() {
+            Some(len) => seed.deserialize(len.into_deserializer()).map(Some),
+            None => (&mut self.elements).next_element_seed(seed),
+        }
+    }
+}
+
+/// The one entry of a map.
+struct Entries<'a> {
+    tracer: &'a mut Tracer,
+    remaining: bool,
+    key: Format,
+    value: Format,
+}
+
+impl<'de, 'a, 'b> MapAccess<'de> for &'b mut Entries<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        if !mem::replace(&mut self.remaining, false) {
+            return Ok(None);
+        }
+        let key = seed.deserialize(&mut *self.tracer)?;
+        self.key = self.tracer.take();
+        Ok(Some(key))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let value = seed.deserialize(&mut *self.tracer)?;
+        self.value = self.tracer.take();
+        Ok(value)
+    }
+}
+
+struct Variants<'a> {
+    tracer: &'a mut Tracer,
+    index: u32,
+}
+
+impl<'de, 'a> EnumAccess<'de> for Variants<'a> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let v = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(self.index))?;
+        Ok((v, self))
+    }
+}
+
+impl<'de, 'a> VariantAccess<'de> for Variants<'a> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        self.tracer.variant = Some(VariantFormat::Unit);
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        let val = seed.deserialize(&mut *self.tracer)?;
+        self.tracer.variant = Some(VariantFormat::Newtype(self.tracer.take()));
+        Ok(val)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        let (val, formats) = self.tracer.seq(len, visitor)?;
+        self.tracer.variant = Some(VariantFormat::Tuple(formats));
+        Ok(val)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        names: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let (val, formats) = self.tracer.seq(names.len(), visitor)?;
+        self.tracer.variant = Some(VariantFormat::Struct(fields(names, formats)));
+        Ok(val)
+    }
+}
+
+/// Works out the largest encoding of a format.
+struct Sizer<'a> {
+    schema: &'a Schema,
+    /// The named types whose size is being worked out, to catch recursion.
+    visiting: Vec<&'a str>,
+}
+
+impl<'a> Sizer<'a> {
+    fn encoding(&self) -> &Encoding {
+        &self.schema.encoding
+    }
+
+    /// The size of a type marker, if there are any.
+    fn marker(&self) -> usize {
+        self.encoding().self_describing as usize
+    }
+
+    /// The size of the marker and length of a tuple or struct, if there are any.
+    fn fixed(&self) -> usize {
+        if self.encoding().self_describing {
+            1 + self.encoding().len_width as usize
+        } else {
+            0
+        }
+    }
+
+    /// The size of a name written out in the self-describing format.
+    fn name(&self, name: &str) -> usize {
+        if self.encoding().self_describing {
+            1 + self.encoding().len_width as usize + name.len()
+        } else {
+            0
+        }
+    }
+
+    fn format(&mut self, format: &'a Format) -> Option<usize> {
+        let marker = self.marker();
+        let size = match *format {
+            Format::Unit => 0,
+            Format::Bool | Format::U8 | Format::I8 => 1,
+            Format::U16 | Format::I16 => 2,
+            Format::U32 | Format::I32 | Format::F32 | Format::Char => 4,
+            Format::U64 | Format::I64 | Format::F64 => 8,
+            Format::U128 | Format::I128 => 16,
+            Format::Usize | Format::Isize => {
+                if self.encoding().self_describing {
+                    8
+                } else {
+                    self.encoding().usize_width as usize
+                }
+            }
+            Format::Str | Format::Bytes | Format::Seq(_) | Format::Map { .. } => return None,
+            // the tag byte doubles as the marker in the self-describing format
+            Format::Option(ref inner) => return self.format(inner)?.checked_add(1),
+            Format::Tuple(ref formats) => return self.all(formats)?.checked_add(self.fixed()),
+            Format::Array { ref element, len } => {
+                return self
+                    .format(element)?
+                    .checked_mul(len)?
+                    .checked_add(self.fixed())
+            }
+            Format::Bounded {
+                ref element,
+                capacity,
+            } => {
+                let prefix = prefix_width(capacity) + marker;
+                let elements = self.format(element)?.checked_mul(capacity)?;
+                return elements.checked_add(prefix + self.fixed());
+            }
+            Format::Named(ref name) => return self.named(name),
+        };
+        Some(size + marker)
+    }
+
+    fn all(&mut self, formats: &'a [Format]) -> Option<usize> {
+        formats
+            .iter()
+            .try_fold(0usize, |acc, f| acc.checked_add(self.format(f)?))
+    }
+
+    fn fields(&mut self, fields: &'a [Field]) -> Option<usize> {
+        fields.iter().try_fold(self.fixed(), |acc, f| {
+            acc.checked_add(self.name(&f.name))?
+                .checked_add(self.format(&f.format)?)
+        })
+    }
+
+    fn named(&mut self, name: &'a str) -> Option<usize> {
+        if self.visiting.contains(&name) {
+            return None;
+        }
+        let container = self.schema.types.get(name)?;
+        self.visiting.push(name);
+        let size = match *container {
+            Container::UnitStruct => Some(self.marker()),
+            Container::NewtypeStruct(ref inner) => self.format(inner),
+            Container::TupleStruct(ref formats) => {
+                self.all(formats).and_then(|n| n.checked_add(self.fixed()))
+            }
+            Container::Struct(ref fields) => self.fields(fields),
+            Container::Enum(ref variants) => self.variants(variants),
+        };
+        self.visiting.pop();
+        size
+    }
+
+    fn variants(&mut self, variants: &'a [Variant]) -> Option<usize> {
+        let header = self.marker() + self.encoding().tag_width as usize;
+        variants.iter().try_fold(0usize, |max, v| {
+            let contents = match v.contents {
+                VariantFormat::Unit => self.marker(),
+                VariantFormat::Newtype(ref inner) => self.format(inner)?,
+                VariantFormat::Tuple(ref formats) => {
+                    self.all(formats)?.checked_add(self.fixed())?
+                }
+                VariantFormat::Struct(ref fields) => self.fields(fields)?,
+            };
+            let size = contents.checked_add(header + self.name(&v.name))?;
+            Some(max.max(size))
+        })
+    }
+}
@@ -0,0 +1,659 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+use serde::Serialize;
+
+use encode_unicode::CharExt;
+
+#[cfg(not(feature = "alloc"))]
+use core::fmt::Display;
+
+#[cfg(not(feature = "alloc"))]
+use ns;
+
+use config::{
+    ByteOrder, DefaultOptions, Format, Mode, Options, Width, CANONICAL_NAN_F32, CANONICAL_NAN_F64,
+};
+use describe::Marker;
+use output::{Output, SliceOutput};
+use {word, Error, ErrorKind, SerializeResult, DEBUG_PANICS};
+
+/// A cursor which serializes values into an `Output`, usually a byte buffer.
+///
+/// Each value is written directly after the previous one, so several values (say, a header and
+/// then a payload) can be packed back-to-back into the same buffer. The free function `serialize`
+/// is a shorthand for serializing a single value at the start of a buffer.
+pub struct Serializer<W, O = DefaultOptions> {
+    out: W,
+    opts: O,
+    word: bool,
+}
+
+impl<'a> Serializer<SliceOutput<'a>> {
+    /// Create a serializer which writes to the start of `buf` using the default options.
+    pub fn new(buf: &'a mut [u8]) -> Serializer<SliceOutput<'a>> {
+        Serializer::with_options(buf, DefaultOptions::new())
+    }
+}
+
+impl<'a, O: Options> Serializer<SliceOutput<'a>, O> {
+    /// Create a serializer which writes to the start of `buf` using the given options.
+    pub fn with_options(buf: &'a mut [u8], opts: O) -> Serializer<SliceOutput<'a>, O> {
+        Serializer::from_output(SliceOutput::new(buf), opts)
+    }
+
+    /// The number of bytes left in the buffer.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.out.remaining()
+    }
+
+    /// Get back the underlying buffer.
+    pub fn into_inner(self) -> &'a mut [u8] {
+        self.out.into_inner()
+    }
+}
+
+impl<W: Output, O: Options> Serializer<W, O> {
+    /// Create a serializer which appends to `out` using the given options.
+    pub fn from_output(out: W, opts: O) -> Serializer<W, O> {
+        Serializer {
+            out,
+            opts,
+            word: false,
+        }
+    }
+
+    /// Serialize a value after everything written so far. Returns the number of bytes used.
+    pub fn serialize<T: Serialize + ?Sized>(&mut self, val: &T) -> SerializeResult<usize> {
+        let start = self.out.position();
+        val.serialize(&mut *self)
+            .map_err(|e| e.at(self.out.position()))?;
+        Ok(self.out.position() - start)
+    }
+
+    /// The number of bytes written so far.
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.out.position()
+    }
+
+    /// The options this serializer encodes with.
+    pub fn options(&self) -> O {
+        self.opts
+    }
+
+    /// Get back the output.
+    pub fn into_output(self) -> W {
+        self.out
+    }
+
+    #[inline]
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.out.write_bytes(bytes)
+    }
+
+    #[inline]
+    pub(crate) fn write_u8(&mut self, val: u8) -> Result<(), Error> {
+        self.write_bytes(&[val])
+    }
+
+    #[inline]
+    fn write_u16(&mut self, val: u16) -> Result<(), Error> {
+        if O::Endian::BIG_ENDIAN {
+            self.write_bytes(&val.to_be_bytes())
+        } else {
+            self.write_bytes(&val.to_le_bytes())
+        }
+    }
+
+    #[inline]
+    fn write_u32(&mut self, val: u32) -> Result<(), Error> {
+        if O::Endian::BIG_ENDIAN {
+            self.write_bytes(&val.to_be_bytes())
+        } else {
+            self.write_bytes(&val.to_le_bytes())
+        }
+    }
+
+    #[inline]
+    fn write_u64(&mut self, val: u64) -> Result<(), Error> {
+        if O::Endian::BIG_ENDIAN {
+            self.write_bytes(&val.to_be_bytes())
+        } else {
+            self.write_bytes(&val.to_le_bytes())
+        }
+    }
+
+    #[inline]
+    fn write_u128(&mut self, val: u128) -> Result<(), Error> {
+        if O::Endian::BIG_ENDIAN {
+            self.write_bytes(&val.to_be_bytes())
+        } else {
+            self.write_bytes(&val.to_le_bytes())
+        }
+    }
+
+    /// Write `val` as an `N`-wide integer, or fail with `overflow` if it doesn't fit.
+    #[inline]
+    fn write_uint<N: Width>(&mut self, val: u64, overflow: ErrorKind) -> Result<(), Error> {
+        match N::BYTES {
+            1 if val <= u8::MAX as u64 => self.write_u8(val as u8),
+            2 if val <= u16::MAX as u64 => self.write_u16(val as u16),
+            4 if val <= u32::MAX as u64 => self.write_u32(val as u32),
+            8 => self.write_u64(val),
+            _ => Err(overflow.into()),
+        }
+    }
+
+    #[inline]
+    fn write_usize(&mut self, val: u64) -> Result<(), Error> {
+        self.write_uint::<O::Usize>(val, ErrorKind::Overflow)
+    }
+
+    #[inline]
+    fn write_isize(&mut self, val: i64) -> Result<(), Error> {
+        if O::Usize::BYTES == 4 {
+            if val < i32::MIN as i64 || val > i32::MAX as i64 {
+                return Err(ErrorKind::Overflow.into());
+            }
+            self.write_u32(val as i32 as u32)
+        } else {
+            self.write_u64(val as u64)
+        }
+    }
+
+    #[inline]
+    pub(crate) fn write_len(&mut self, len: usize) -> Result<(), Error> {
+        self.write_uint::<O::Len>(len as u64, ErrorKind::Overflow)
+    }
+
+    /// Write the type marker of the next value, in the self-describing format.
+    #[inline]
+    pub(crate) fn mark(&mut self, marker: Marker) -> Result<(), Error> {
+        if O::Format::DESCRIBED {
+            self.write_u8(marker as u8)?;
+        }
+        Ok(())
+    }
+
+    /// Start a tuple or struct, which only has a marker and length in the self-describing format.
+    #[inline]
+    pub(crate) fn start_fixed(&mut self, marker: Marker, len: usize) -> Result<(), Error> {
+        if O::Format::DESCRIBED {
+            // struct fields are named like map keys, and decode in any order
+            if O::Mode::CANONICAL && marker == Marker::Map {
+                return Err(ErrorKind::NonCanonicalMap.into());
+            }
+            self.write_u8(marker as u8)?;
+            self.write_len(len)?;
+        }
+        Ok(())
+    }
+
+    /// Write the discriminant of an enum variant, and in the self-describing format its name.
+    pub(crate) fn write_variant(
+        &mut self,
+        name: &str,
+        variant_index: u32,
+        variant: &str,
+    ) -> Result<(), Error> {
+        self.mark(Marker::Enum)?;
+        self.write_tag(name, variant_index)?;
+        if O::Format::DESCRIBED {
+            serde::Serializer::serialize_str(&mut *self, variant)?;
+        }
+        Ok(())
+    }
+
+    /// Write a length-prefixed string or byte string.
+    #[inline]
+    fn write_prefixed(&mut self, v: &[u8]) -> Result<(), Error> {
+        self.out.reserve(v.len().saturating_add(O::Len::BYTES))?;
+        self.write_len(v.len())?;
+        self.write_bytes(v)
+    }
+
+    /// Start a sequence or map, writing its length now if it's known, or a placeholder to be
+    /// patched once its elements have been counted otherwise.
+    #[inline]
+    fn start_seq(&mut self, len: Option<usize>) -> Result<Compound<'_, W, O>, Error> {
+        let start = self.position();
+        self.write_len(len.unwrap_or(0))?;
+        Ok(Compound {
+            ser: self,
+            start,
+            len,
+            count: 0,
+        })
+    }
+
+    /// Fill in the length placeholder at `slot`.
+    fn patch_len(&mut self, slot: usize, len: usize) -> Result<(), Error> {
+        let mut bytes = [0; 8];
+        let mut tmp = Serializer::with_options(&mut bytes, self.opts);
+        tmp.write_len(len)?;
+        let width = tmp.position();
+        self.out.patch(slot, &bytes[..width])
+    }
+
+    #[inline]
+    fn write_tag(&mut self, name: &str, variant_index: u32) -> Result<(), Error> {
+        let res = self.write_uint::<O::Tag>(variant_index as u64, ErrorKind::TooManyVariants);
+        if let Err(ref e) = res {
+            if DEBUG_PANICS && *e.kind() == ErrorKind::TooManyVariants {
+                panic!("too many enum variants: {}", name);
+            }
+        }
+        res
+    }
+}
+
+impl<'a, W: Output, O: Options> serde::Serializer for &'a mut Serializer<W, O> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Compound<'a, W, O>;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Compound<'a, W, O>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    /// Lets types like `IpAddr` pick their compact binary form rather than a string.
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn serialize_bool(self, v: bool) -> SerializeResult<()> {
+        self.mark(Marker::Bool)?;
+        self.write_u8(if v { 1 } else { 0 })
+    }
+
+    #[inline]
+    fn serialize_u8(self, v: u8) -> SerializeResult<()> {
+        self.mark(Marker::U8)?;
+        self.write_u8(v)
+    }
+
+    #[inline]
+    fn serialize_u16(self, v: u16) -> SerializeResult<()> {
+        self.mark(Marker::U16)?;
+        self.write_u16(v)
+    }
+
+    #[inline]
+    fn serialize_u32(self, v: u32) -> SerializeResult<()> {
+        self.mark(Marker::U32)?;
+        self.write_u32(v)
+    }
+
+    #[inline]
+    fn serialize_u64(self, v: u64) -> SerializeResult<()> {
+        self.mark(Marker::U64)?;
+        if self.word {
+            self.word = false;
+            return self.write_usize(v);
+        }
+        self.write_u64(v)
+    }
+
+    #[inline]
+    fn serialize_u128(self, v: u128) -> SerializeResult<()> {
+        self.mark(Marker::U128)?;
+        self.write_u128(v)
+    }
+
+    #[inline]
+    fn serialize_i8(self, v: i8) -> SerializeResult<()> {
+        self.mark(Marker::I8)?;
+        self.write_u8(v as u8)
+    }
+
+    #[inline]
+    fn serialize_i16(self, v: i16) -> SerializeResult<()> {
+        self.mark(Marker::I16)?;
+        self.write_u16(v as u16)
+    }
+
+    #[inline]
+    fn serialize_i32(self, v: i32) -> SerializeResult<()> {
+        self.mark(Marker::I32)?;
+        self.write_u32(v as u32)
+    }
+
+    #[inline]
+    fn serialize_i64(self, v: i64) -> SerializeResult<()> {
+        self.mark(Marker::I64)?;
+        if self.word {
+            self.word = false;
+            return self.write_isize(v);
+        }
+        self.write_u64(v as u64)
+    }
+
+    #[inline]
+    fn serialize_i128(self, v: i128) -> SerializeResult<()> {
+        self.mark(Marker::I128)?;
+        self.write_u128(v as u128)
+    }
+
+    #[inline]
+    fn serialize_f32(self, v: f32) -> SerializeResult<()> {
+        self.mark(Marker::F32)?;
+        if O::Mode::CANONICAL && v.is_nan() {
+            return self.write_u32(CANONICAL_NAN_F32);
+        }
+        self.write_u32(v.to_bits())
+    }
+
+    #[inline]
+    fn serialize_f64(self, v: f64) -> SerializeResult<()> {
+        self.mark(Marker::F64)?;
+        if O::Mode::CANONICAL && v.is_nan() {
+            return self.write_u64(CANONICAL_NAN_F64);
+        }
+        self.write_u64(v.to_bits())
+    }
+
+    fn serialize_str(self, v: &str) -> SerializeResult<()> {
+        self.mark(Marker::Str)?;
+        self.write_prefixed(v.as_bytes())
+    }
+
+    fn serialize_char(self, c: char) -> SerializeResult<()> {
+        self.mark(Marker::Char)?;
+        let (arr, sz) = c.to_utf8_array();
+        self.write_bytes(&arr[..sz])
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> SerializeResult<()> {
+        self.mark(Marker::Bytes)?;
+        self.write_prefixed(v)
+    }
+
+    #[inline]
+    fn serialize_none(self) -> SerializeResult<()> {
+        if O::Format::DESCRIBED {
+            return self.mark(Marker::None);
+        }
+        self.write_u8(0)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, v: &T) -> SerializeResult<()> {
+        if O::Format::DESCRIBED {
+            self.mark(Marker::Some)?;
+        } else {
+            self.write_u8(1)?;
+        }
+        v.serialize(self)
+    }
+
+    #[inline]
+    fn serialize_unit(self) -> SerializeResult<()> {
+        self.mark(Marker::Unit)
+    }
+
+    #[inline]
+    fn serialize_unit_struct(self, _: &'static str) -> SerializeResult<()> {
+        self.mark(Marker::Unit)
+    }
+
+    #[inline]
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> SerializeResult<()> {
+        self.write_variant(name, variant_index, variant)?;
+        self.mark(Marker::Unit)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> SerializeResult<()> {
+        // words are plain 64-bit integers in the self-describing format
+        if !O::Format::DESCRIBED && (name == word::USIZE_TOKEN || name == word::ISIZE_TOKEN) {
+            self.word = true;
+        }
+        let res = value.serialize(&mut *self);
+        // cleared in case the value wasn't the `u64` or `i64` the word was expected to be
+        self.word = false;
+        res
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> SerializeResult<()> {
+        self.write_variant(name, variant_index, variant)?;
+        value.serialize(self)
+    }
+
+    #[inline]
+    fn serialize_seq(self, len: Option<usize>) -> SerializeResult<Compound<'a, W, O>> {
+        self.mark(Marker::Seq)?;
+        self.start_seq(len)
+    }
+
+    #[inline]
+    fn serialize_tuple(self, len: usize) -> SerializeResult<Self> {
+        self.start_fixed(Marker::Seq, len)?;
+        Ok(self)
+    }
+
+    #[inline]
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> SerializeResult<Self> {
+        self.start_fixed(Marker::Seq, len)?;
+        Ok(self)
+    }
+
+    #[inline]
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> SerializeResult<Self> {
+        self.write_variant(name, variant_index, variant)?;
+        self.start_fixed(Marker::Seq, len)?;
+        Ok(self)
+    }
+
+    #[inline]
+    fn serialize_map(self, len: Option<usize>) -> SerializeResult<Compound<'a, W, O>> {
+        if O::Mode::CANONICAL {
+            return Err(ErrorKind::NonCanonicalMap.into());
+        }
+        self.mark(Marker::Map)?;
+        self.start_seq(len)
+    }
+
+    #[inline]
+    fn serialize_struct(self, _name: &'static str, len: usize) -> SerializeResult<Self> {
+        self.start_fixed(Marker::Map, len)?;
+        Ok(self)
+    }
+
+    #[inline]
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> SerializeResult<Self> {
+        self.write_variant(name, variant_index, variant)?;
+        self.start_fixed(Marker::Map, len)?;
+        Ok(self)
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn collect_str<T: Display + ?Sized>(self, _value: &T) -> SerializeResult<()> {
+        ns()
+    }
+}
+
+/// Serializes the elements of a sequence or map, counting them. If its length wasn't known up
+/// front it's patched in at the end, so the output must support `Output::patch`, and otherwise
+/// the count must match it.
+pub struct Compound<'a, W, O> {
+    ser: &'a mut Serializer<W, O>,
+    // where the length, or the placeholder for it, was written
+    start: usize,
+    len: Option<usize>,
+    count: usize,
+}
+
+impl<'a, W: Output, O: Options> Compound<'a, W, O> {
+    #[inline]
+    fn end(self) -> SerializeResult<()> {
+        match self.len {
+            None => self
+                .ser
+                .patch_len(self.start, self.count)
+                .map_err(|e| e.at(self.start)),
+            Some(len) if len != self.count => {
+                if DEBUG_PANICS {
+                    panic!("sequence of {} elements serialized as {}", self.count, len);
+                }
+                let kind = ErrorKind::LengthMismatch {
+                    len,
+                    count: self.count,
+                };
+                Err(Error::new(kind).at(self.start))
+            }
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+impl<'a, W: Output, O: Options> serde::ser::SerializeSeq for Compound<'a, W, O> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<V: Serialize + ?Sized>(&mut self, value: &V) -> SerializeResult<()> {
+        self.count += 1;
+        value.serialize(&mut *self.ser)
+    }
+
+    #[inline]
+    fn end(self) -> SerializeResult<()> {
+        Compound::end(self)
+    }
+}
+
+impl<W: Output, O: Options> serde::ser::SerializeTuple for &mut Serializer<W, O> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<V: Serialize + ?Sized>(&mut self, value: &V) -> SerializeResult<()> {
+        value.serialize(&mut **self)
+    }
+
+    #[inline]
+    fn end(self) -> SerializeResult<()> {
+        Ok(())
+    }
+}
+
+impl<W: Output, O: Options> serde::ser::SerializeTupleStruct for &mut Serializer<W, O> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<V: Serialize + ?Sized>(&mut self, value: &V) -> Result<(), Error> {
+        value.serialize(&mut **self)
+    }
+
+    #[inline]
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<W: Output, O: Options> serde::ser::SerializeTupleVariant for &mut Serializer<W, O> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<V: Serialize + ?Sized>(&mut self, value: &V) -> Result<(), Error> {
+        value.serialize(&mut **self)
+    }
+
+    #[inline]
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a, W: Output, O: Options> serde::ser::SerializeMap for Compound<'a, W, O> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<K: Serialize + ?Sized>(&mut self, key: &K) -> Result<(), Error> {
+        self.count += 1;
+        key.serialize(&mut *self.ser)
+    }
+
+    fn serialize_value<V: Serialize + ?Sized>(&mut self, value: &V) -> Result<(), Error> {
+        value.serialize(&mut *self.ser)
+    }
+
+    #[inline]
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl<W: Output, O: Options> serde::ser::SerializeStruct for &mut Serializer<W, O> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<V: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &V,
+    ) -> Result<(), Error> {
+        if O::Format::DESCRIBED {
+            serde::Serializer::serialize_str(&mut **self, key)?;
+        }
+        value.serialize(&mut **self)
+    }
+
+    #[inline]
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<W: Output, O: Options> serde::ser::SerializeStructVariant for &mut Serializer<W, O> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<V: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &V,
+    ) -> Result<(), Error> {
+        if O::Format::DESCRIBED {
+            serde::Serializer::serialize_str(&mut **self, key)?;
+        }
+        value.serialize(&mut **self)
+    }
+
+    #[inline]
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
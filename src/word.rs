@@ -0,0 +1,103 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+//! Encode `usize` and `isize` fields with the configured word width.
+//!
+//! serde hands every `usize` to the serializer as a `u64` (and every `isize` as an `i64`), so by
+//! default they take 8 bytes on the wire no matter what `Options::Usize` says. Fields marked with
+//! `#[serde(with = "ssmarshal::word")]` are instead encoded with the width chosen by
+//! `Config::with_usize`, which lets 32-bit peers exchange them as 4 bytes. Other serde formats
+//! see such fields as a plain newtype around the integer.
+
+use core::fmt;
+
+use serde::de::{Deserializer, Error, Unexpected, Visitor};
+use serde::Serializer;
+
+#[doc(hidden)]
+pub const USIZE_TOKEN: &str = "$ssmarshal::private::usize";
+#[doc(hidden)]
+pub const ISIZE_TOKEN: &str = "$ssmarshal::private::isize";
+
+/// Integers which can be encoded as words: `usize` and `isize`.
+pub trait Word: Sized + Copy {
+    #[doc(hidden)]
+    fn serialize_word<S: Serializer>(self, serializer: S) -> Result<S::Ok, S::Error>;
+    #[doc(hidden)]
+    fn deserialize_word<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>;
+}
+
+/// Serialize a `usize` or `isize` as a word.
+pub fn serialize<W: Word, S: Serializer>(val: &W, serializer: S) -> Result<S::Ok, S::Error> {
+    val.serialize_word(serializer)
+}
+
+/// Deserialize a `usize` or `isize` encoded as a word.
+pub fn deserialize<'de, W: Word, D: Deserializer<'de>>(deserializer: D) -> Result<W, D::Error> {
+    W::deserialize_word(deserializer)
+}
+
+struct UsizeVisitor;
+
+impl<'de> Visitor<'de> for UsizeVisitor {
+    type Value = usize;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a usize")
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<usize, E> {
+        if v > usize::MAX as u64 {
+            return Err(E::invalid_value(Unexpected::Unsigned(v), &self));
+        }
+        Ok(v as usize)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, d: D) -> Result<usize, D::Error> {
+        d.deserialize_u64(self)
+    }
+}
+
+struct IsizeVisitor;
+
+impl<'de> Visitor<'de> for IsizeVisitor {
+    type Value = isize;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an isize")
+    }
+
+    fn visit_i64<E: Error>(self, v: i64) -> Result<isize, E> {
+        if v > isize::MAX as i64 || v < isize::MIN as i64 {
+            return Err(E::invalid_value(Unexpected::Signed(v), &self));
+        }
+        Ok(v as isize)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, d: D) -> Result<isize, D::Error> {
+        d.deserialize_i64(self)
+    }
+}
+
+impl Word for usize {
+    fn serialize_word<S: Serializer>(self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(USIZE_TOKEN, &(self as u64))
+    }
+
+    fn deserialize_word<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+        deserializer.deserialize_newtype_struct(USIZE_TOKEN, UsizeVisitor)
+    }
+}
+
+impl Word for isize {
+    fn serialize_word<S: Serializer>(self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(ISIZE_TOKEN, &(self as i64))
+    }
+
+    fn deserialize_word<'de, D: Deserializer<'de>>(deserializer: D) -> Result<isize, D::Error> {
+        deserializer.deserialize_newtype_struct(ISIZE_TOKEN, IsizeVisitor)
+    }
+}
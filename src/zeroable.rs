@@ -0,0 +1,77 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+use core::marker::PhantomData;
+use core::num::{
+    NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize, NonZeroU128,
+    NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize,
+};
+
+/// Types for which all-zero memory is a valid value.
+///
+/// `deserialize_uninit` zeroes its slot and then decodes in place over that value, so it needs
+/// this to never hand out a `&mut T` to invalid memory.
+///
+/// # Safety
+///
+/// Implementing this for a type with any field where zero isn't valid, like a reference, a `Box`
+/// or a `NonZeroU32`, is undefined behavior. A struct is fine to mark when all of its fields are
+/// `Zeroable`:
+///
+/// ```rust
+/// # extern crate ssmarshal;
+/// struct Header {
+///     id: u32,
+///     flags: [bool; 4],
+/// }
+///
+/// unsafe impl ssmarshal::Zeroable for Header {}
+/// # fn main() {}
+/// ```
+pub unsafe trait Zeroable {}
+
+macro_rules! zeroable {
+    ($($t:ty),* $(,)*) => {
+        $(unsafe impl Zeroable for $t {})*
+    }
+}
+
+zeroable! {
+    (), bool, char, f32, f64,
+    u8, u16, u32, u64, u128, usize,
+    i8, i16, i32, i64, i128, isize,
+    Option<NonZeroU8>, Option<NonZeroU16>, Option<NonZeroU32>, Option<NonZeroU64>,
+    Option<NonZeroU128>, Option<NonZeroUsize>,
+    Option<NonZeroI8>, Option<NonZeroI16>, Option<NonZeroI32>, Option<NonZeroI64>,
+    Option<NonZeroI128>, Option<NonZeroIsize>,
+}
+
+unsafe impl<T: ?Sized> Zeroable for PhantomData<T> {}
+
+unsafe impl<T: Zeroable, const N: usize> Zeroable for [T; N] {}
+
+macro_rules! zeroable_tuple {
+    ($($name:ident)+) => {
+        unsafe impl<$($name: Zeroable),+> Zeroable for ($($name,)+) {}
+    }
+}
+
+zeroable_tuple!(T0);
+zeroable_tuple!(T0 T1);
+zeroable_tuple!(T0 T1 T2);
+zeroable_tuple!(T0 T1 T2 T3);
+zeroable_tuple!(T0 T1 T2 T3 T4);
+zeroable_tuple!(T0 T1 T2 T3 T4 T5);
+zeroable_tuple!(T0 T1 T2 T3 T4 T5 T6);
+zeroable_tuple!(T0 T1 T2 T3 T4 T5 T6 T7);
+zeroable_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8);
+zeroable_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8 T9);
+zeroable_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10);
+zeroable_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11);
+zeroable_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12);
+zeroable_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12 T13);
+zeroable_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12 T13 T14);
+zeroable_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12 T13 T14 T15);
@@ -0,0 +1,94 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+//! `#[derive(MaxSize)]` for ssmarshal. Use it through the `derive` feature of the `ssmarshal`
+//! crate rather than depending on this crate directly.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, GenericParam};
+
+#[proc_macro_derive(MaxSize)]
+pub fn derive_max_size(input: TokenStream) -> TokenStream {
+    let mut input = parse_macro_input!(input as DeriveInput);
+
+    for param in &mut input.generics.params {
+        if let GenericParam::Type(ref mut ty) = *param {
+            ty.bounds.push(parse_quote!(::ssmarshal::MaxSize));
+        }
+    }
+
+    let body = match input.data {
+        Data::Struct(ref data) => fields_size(&data.fields),
+        Data::Enum(ref data) if data.variants.is_empty() => quote!(0),
+        Data::Enum(ref data) => {
+            let variants = data.variants.iter().map(|v| fields_size(&v.fields));
+            // one byte for the discriminant, then the largest variant
+            quote! {
+                1 + {
+                    let mut max = 0;
+                    #(
+                        let variant = #variants;
+                        if variant > max {
+                            max = variant;
+                        }
+                    )*
+                    max
+                }
+            }
+        }
+        Data::Union(_) => {
+            return syn::Error::new_spanned(&input.ident, "unions cannot derive MaxSize")
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let expanded = quote! {
+        impl #impl_generics ::ssmarshal::MaxSize for #name #ty_generics #where_clause {
+            const MAX_SIZE: usize = #body;
+        }
+    };
+    expanded.into()
+}
+
+/// Sum of the sizes of all fields that serde will actually serialize.
+fn fields_size(fields: &Fields) -> TokenStream2 {
+    let tys = fields
+        .iter()
+        .filter(|f| !is_skipped(&f.attrs))
+        .map(|f| &f.ty);
+    quote!(0 #(+ <#tys as ::ssmarshal::MaxSize>::MAX_SIZE)*)
+}
+
+/// Whether a field carries `#[serde(skip)]` and so never hits the wire.
+fn is_skipped(attrs: &[syn::Attribute]) -> bool {
+    let mut skipped = false;
+    for attr in attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skipped = true;
+            } else if meta.input.peek(syn::Token![=]) {
+                // consume the value of attributes like `rename = "..."`
+                meta.value()?.parse::<syn::Expr>()?;
+            } else if meta.input.peek(syn::token::Paren) {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                content.parse::<TokenStream2>()?;
+            }
+            Ok(())
+        });
+    }
+    skipped
+}
@@ -0,0 +1,77 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+#![cfg(feature = "alloc")]
+
+extern crate quickcheck;
+extern crate quickcheck_macros;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate ssmarshal;
+
+use std::collections::BTreeMap;
+
+use quickcheck_macros::quickcheck;
+
+use ssmarshal::{deserialize_borrowed, to_vec};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Manifest {
+    name: String,
+    files: BTreeMap<String, Vec<u8>>,
+    parent: Option<Box<Manifest>>,
+    tags: Vec<(u8, char)>,
+}
+
+fn rt<T: serde::Serialize + serde::de::DeserializeOwned + PartialEq>(val: &T) -> bool {
+    let buf = to_vec(val).unwrap();
+    let (new_val, len): (T, usize) = deserialize_borrowed(&buf).unwrap();
+    val == &new_val && len == buf.len()
+}
+
+#[test]
+fn manifest() {
+    let mut files = BTreeMap::new();
+    files.insert("a".to_string(), vec![1, 2, 3]);
+    files.insert("b".to_string(), vec![]);
+    let val = Manifest {
+        name: "child".to_string(),
+        files,
+        parent: Some(Box::new(Manifest {
+            name: "root".to_string(),
+            files: BTreeMap::new(),
+            parent: None,
+            tags: vec![(1, 'x')],
+        })),
+        tags: vec![],
+    };
+    assert!(rt(&val));
+}
+
+#[test]
+fn exact_size() {
+    let buf = to_vec(&"hello".to_string()).unwrap();
+    assert_eq!(buf, b"\x05\0\0\0\0\0\0\0hello");
+    let buf = to_vec(&vec![1u16, 2]).unwrap();
+    assert_eq!(buf, &[2, 0, 0, 0, 0, 0, 0, 0, 1, 0, 2, 0]);
+}
+
+#[quickcheck]
+fn rt_string(val: String) -> bool {
+    rt(&val)
+}
+
+#[quickcheck]
+fn rt_vec(val: Vec<Option<i64>>) -> bool {
+    rt(&val)
+}
+
+#[quickcheck]
+fn rt_map(val: BTreeMap<u32, String>) -> bool {
+    rt(&val)
+}
@@ -0,0 +1,52 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate ssmarshal;
+
+use ssmarshal::{deserialize_borrowed, ErrorKind, Serializer};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Request<'a> {
+    id: u32,
+    path: &'a str,
+    body: &'a [u8],
+    tag: Option<&'a str>,
+}
+
+#[test]
+fn zero_copy() {
+    let req = Request {
+        id: 5,
+        path: "/dev/null",
+        body: &[0xDE, 0xAD, 0xBE, 0xEF],
+        tag: Some("ünïcödé"),
+    };
+    let mut buf = [0u8; 128];
+    let len = Serializer::new(&mut buf).serialize(&req).unwrap();
+    assert_eq!(len, 4 + 8 + 9 + 8 + 4 + 1 + 8 + "ünïcödé".len());
+    assert_eq!(&buf[4..12], &[9, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(&buf[12..21], b"/dev/null");
+
+    let (new_req, new_len): (Request, usize) = deserialize_borrowed(&buf[..len]).unwrap();
+    assert_eq!(new_req, req);
+    assert_eq!(new_len, len);
+    assert_eq!(new_req.path.as_ptr(), buf[12..].as_ptr());
+    assert_eq!(new_req.body.as_ptr(), buf[29..].as_ptr());
+}
+
+#[test]
+fn invalid_utf8() {
+    let buf = [2, 0, 0, 0, 0, 0, 0, 0, 0xC3, 0x28];
+    let err = deserialize_borrowed::<&str>(&buf).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::InvalidUtf8);
+    assert_eq!(err.offset(), Some(0));
+    let (bytes, _) = deserialize_borrowed::<&[u8]>(&buf).unwrap();
+    assert_eq!(bytes, &[0xC3, 0x28]);
+}
@@ -0,0 +1,173 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+extern crate arrayvec;
+extern crate heapless;
+extern crate quickcheck;
+extern crate quickcheck_macros;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate ssmarshal;
+
+mod common;
+
+use arrayvec::{ArrayString, ArrayVec};
+use quickcheck_macros::quickcheck;
+
+use ssmarshal::config::BigEndian;
+use ssmarshal::{deserialize, Config, ErrorKind, MaxSize};
+
+use common::roundtrip;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, MaxSize)]
+struct Packet {
+    #[serde(with = "ssmarshal::bounded::heapless_vec")]
+    payload: heapless::Vec<u16, 8>,
+    #[serde(with = "ssmarshal::bounded::heapless_string")]
+    name: heapless::String<300>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, MaxSize)]
+struct Record {
+    #[serde(with = "ssmarshal::bounded::array_vec")]
+    ids: ArrayVec<u32, 4>,
+    #[serde(with = "ssmarshal::bounded::array_string")]
+    label: ArrayString<12>,
+}
+
+fn packet() -> Packet {
+    Packet {
+        payload: heapless::Vec::from_slice(&[1, 2]).unwrap(),
+        name: "ünï".parse().unwrap(),
+    }
+}
+
+#[test]
+fn heapless_prefixes() {
+    assert_eq!(
+        roundtrip(&packet(), Config::new()),
+        b"\x02\x01\x00\x02\x00\x05\x00\xc3\xbcn\xc3\xaf"
+    );
+}
+
+#[test]
+fn prefix_follows_byte_order() {
+    let bytes = roundtrip(&packet(), Config::new().with_endian::<BigEndian>());
+    assert_eq!(&bytes[5..7], &[0, 5]);
+    assert_eq!(bytes.len(), 12);
+}
+
+#[test]
+fn arrayvec_prefixes() {
+    let mut val = Record {
+        ids: ArrayVec::new(),
+        label: ArrayString::from("ok").unwrap(),
+    };
+    val.ids.push(0xAABBCCDD);
+    assert_eq!(
+        roundtrip(&val, Config::new()),
+        b"\x01\xdd\xcc\xbb\xaa\x02ok"
+    );
+}
+
+/// The same collections without `ssmarshal::bounded`, using their own serde impls.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, MaxSize)]
+struct Plain {
+    payload: heapless::Vec<u8, 4>,
+    name: heapless::String<3>,
+    ids: ArrayVec<u32, 2>,
+    label: ArrayString<2>,
+}
+
+#[test]
+fn max_size() {
+    // the derive can't see `with`, so every collection counts a full 8-byte prefix
+    assert_eq!(Packet::MAX_SIZE, 8 + 8 * 2 + 8 + 300);
+    assert_eq!(Record::MAX_SIZE, 8 + 4 * 4 + 8 + 12);
+    assert_eq!(Plain::MAX_SIZE, 8 + 4 + 8 + 3 + 8 + 2 * 4 + 8 + 2);
+}
+
+#[test]
+fn plain_fields_fit() {
+    let val = Plain {
+        payload: heapless::Vec::from_slice(&[1, 2, 3, 4]).unwrap(),
+        name: "abc".parse().unwrap(),
+        ids: [7, 8].iter().cloned().collect(),
+        label: ArrayString::from("xy").unwrap(),
+    };
+    assert_eq!(roundtrip(&val, Config::new()).len(), Plain::MAX_SIZE);
+}
+
+#[test]
+fn over_capacity() {
+    let buf = [
+        9, 1, 0, 2, 0, 3, 0, 4, 0, 5, 0, 6, 0, 7, 0, 8, 0, 9, 0, 0, 0,
+    ];
+    let err = deserialize::<Packet>(&buf).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::CapacityExceeded);
+    assert_eq!(err.offset(), Some(0));
+    let err = deserialize::<Record>(&[0, 13]).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::CapacityExceeded);
+    assert_eq!(err.offset(), Some(1));
+    assert_eq!(err.path().to_string(), ".label");
+}
+
+#[test]
+fn invalid_utf8() {
+    assert!(deserialize::<Packet>(&[0, 2, 0, 0xC3, 0x28]).is_err());
+    assert!(deserialize::<Record>(&[0, 1, 0xFF]).is_err());
+}
+
+#[quickcheck]
+fn rt_heapless(val: Vec<u16>, name: String) -> bool {
+    let mut val = Packet {
+        payload: val.into_iter().take(8).collect(),
+        name: heapless::String::new(),
+    };
+    for c in name.chars() {
+        if val.name.push(c).is_err() {
+            break;
+        }
+    }
+    roundtrip(&val, Config::new()).len() <= Packet::MAX_SIZE
+}
+
+#[test]
+fn self_describing() {
+    use ssmarshal::config::SelfDescribing;
+
+    let opts = Config::new().with_format::<SelfDescribing>();
+    let record = Record {
+        ids: [5, 6].iter().cloned().collect(),
+        label: ArrayString::from("tag").unwrap(),
+    };
+    roundtrip(&(packet(), record), opts);
+}
+
+#[cfg(not(feature = "debug-panics"))]
+#[test]
+fn self_describing_over_capacity() {
+    use ssmarshal::config::SelfDescribing;
+    use ssmarshal::{serialize_with, Deserializer};
+
+    /// Like `Record`, but with room for more `ids`.
+    #[derive(Serialize)]
+    struct Wide {
+        #[serde(with = "ssmarshal::bounded::array_vec")]
+        ids: ArrayVec<u32, 5>,
+    }
+
+    let opts = Config::new().with_format::<SelfDescribing>();
+    let mut ids = ArrayVec::<u32, 5>::new();
+    ids.extend([1, 2, 3, 4, 5].iter().cloned());
+    let mut buf = [0u8; 256];
+    let len = serialize_with(&mut buf, &Wide { ids }, opts).unwrap();
+    let mut de = Deserializer::with_options(&buf[..len], opts);
+    let err = <Record as serde::Deserialize>::deserialize(&mut de).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::CapacityExceeded);
+}
@@ -0,0 +1,211 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+#![cfg(feature = "cli")]
+
+extern crate serde;
+
+extern crate ssmarshal;
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+use serde::{Deserialize, Serialize};
+
+use ssmarshal::schema::{self, Schema};
+
+#[derive(Serialize, Deserialize)]
+enum Shape {
+    Empty,
+    Circle(u8),
+    Rect { w: u8, h: u8 },
+}
+
+#[derive(Serialize, Deserialize)]
+struct Frame {
+    id: u16,
+    shapes: Vec<Shape>,
+}
+
+/// Write the schema of `Frame` to a file, as JSON or encoded.
+fn schema_file(name: &str, json: bool) -> PathBuf {
+    write_schema(name, json, schema::trace::<Frame>().unwrap())
+}
+
+fn write_schema(name: &str, json: bool, schema: Schema) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("ssmarshal-cli-{}", name));
+    if json {
+        fs::write(&path, schema.to_json()).unwrap();
+    } else {
+        fs::write(&path, ssmarshal::to_vec(&schema).unwrap()).unwrap();
+    }
+    path
+}
+
+fn run(args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ssmarshal"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn frame() -> Vec<u8> {
+    let frame = Frame {
+        id: 0x102,
+        shapes: vec![Shape::Empty, Shape::Circle(5), Shape::Rect { w: 2, h: 3 }],
+    };
+    let mut buf = [0u8; 64];
+    let len = ssmarshal::serialize(&mut buf, &frame).unwrap();
+    buf[..len].to_vec()
+}
+
+#[test]
+fn decode_and_encode() {
+    let schema = schema_file("json", true);
+    let schema = schema.to_str().unwrap();
+    let out = run(&["decode", schema], &frame());
+    assert!(out.status.success());
+    let json = String::from_utf8(out.stdout).unwrap();
+    assert!(json.contains("\"Circle\": 5"), "{}", json);
+
+    let out = run(&["encode", schema, "-"], json.as_bytes());
+    assert!(out.status.success());
+    assert_eq!(out.stdout, frame());
+}
+
+#[test]
+fn hex_and_tree() {
+    let schema = schema_file("encoded", false);
+    let schema = schema.to_str().unwrap();
+    let hex = "0x02 01 03 00 00 00 00 00\n00 00 00 01 05 02 02 03\n";
+    let out = run(&["decode", "--hex", "--tree", schema], hex.as_bytes());
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "\
+0..16       {}
+0..2          id: 258
+2..16         shapes: [3]
+10..11          0: Empty
+11..13          1: Circle
+12..13            5
+13..16          2: Rect
+14..15            w: 2
+15..16            h: 3
+"
+    );
+
+    let json = run(
+        &["decode", "--hex", schema],
+        b"01 00 01 00 00 00 00 00 00 00 00",
+    )
+    .stdout;
+    assert!(!json.is_empty());
+    for hex in &[
+        "0x01 0x00 01,00,00:00 0X0000000000",
+        "0100 0100000000000000 00",
+    ] {
+        let out = run(&["decode", "--hex", schema], hex.as_bytes());
+        assert_eq!(out.stdout, json);
+    }
+    let out = run(&["decode", "--hex", schema], b"0x01 0x0 00");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "error: odd number of hex digits in `0x0`\n"
+    );
+
+    let out = run(
+        &["encode", "--hex", schema],
+        br#"{"id": 1, "shapes": ["Empty"]}"#,
+    );
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "01 00 01 00 00 00 00 00 00 00 00\n"
+    );
+}
+
+#[cfg(not(feature = "debug-panics"))]
+#[test]
+fn errors() {
+    let schema = schema_file("errors", true);
+    let schema = schema.to_str().unwrap();
+    let mut bytes = frame();
+
+    let out = run(&["decode", schema], &bytes[..bytes.len() - 1]);
+    assert!(!out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "error: end of stream reached but more data was needed at byte 15\n"
+    );
+
+    bytes.push(0);
+    let out = run(&["decode", schema], &bytes);
+    assert!(!out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "error: trailing bytes after the value at byte 16\n"
+    );
+    assert!(out.stdout.is_empty());
+
+    bytes[13] = 7;
+    let out = run(&["decode", schema], &bytes);
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "error: unknown enum variant 7 at byte 13\n"
+    );
+
+    let out = run(&["encode", schema], br#"{"id": 1, "shapes": [}"#);
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "error: <stdin>: expected value at byte 21\n"
+    );
+}
+
+/// Captures and JSON nested deeper than the tool allows fail with an error rather than running
+/// out of stack.
+#[cfg(not(feature = "debug-panics"))]
+#[test]
+fn deep_input() {
+    #[allow(dead_code)]
+    #[derive(Serialize, Deserialize)]
+    enum List {
+        Cons(u8, Box<List>),
+        Nil,
+    }
+
+    let schema = write_schema("list", true, schema::trace::<List>().unwrap());
+    let schema = schema.to_str().unwrap();
+    let list = |depth: usize| {
+        let mut bytes = [0, 7].repeat(depth);
+        bytes.push(1);
+        bytes
+    };
+    let out = run(&["decode", schema], &list(128));
+    assert!(out.status.success());
+    let out = run(&["encode", schema], &out.stdout);
+    assert_eq!(out.stdout, list(128));
+
+    let out = run(&["decode", schema], &list(100_000));
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "error: depth limit exceeded at byte 257\n"
+    );
+
+    let deep = "[".repeat(200_000) + &"]".repeat(200_000);
+    let out = run(&["encode", schema], deep.as_bytes());
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "error: <stdin>: nested too deeply at byte 256\n"
+    );
+}
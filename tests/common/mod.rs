@@ -0,0 +1,87 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+//! Helpers shared by the integration tests, each of which includes them with `mod common;`.
+
+// no one test uses all of them
+#![allow(dead_code)]
+
+use std::fmt::Debug;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use ssmarshal::{deserialize_with, serialize_with, serialized_size_with, Config, Options};
+
+/// The header of a `Message`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Header {
+    pub badge: u16,
+    pub urgent: bool,
+}
+
+/// An operation, with unit, struct and tuple variants.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Op {
+    Nop,
+    Map { vaddr: u64, frame: u32 },
+    Signal(char, Option<u16>),
+}
+
+/// A value mixing most kinds of field, for tests which don't care about its shape.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub header: Header,
+    pub ops: [Op; 3],
+    pub reply: Option<i32>,
+}
+
+/// A `Message`, which is 28 bytes with the default options.
+///
+/// The `urgent` flag is at byte 2, the `Signal` tag at 16, its `char` at 17 and its `Option` tag
+/// at 19.
+pub fn message() -> Message {
+    Message {
+        header: Header {
+            badge: 0xBEEF,
+            urgent: true,
+        },
+        ops: [
+            Op::Map {
+                vaddr: 0x1234_5678_9ABC_DEF0,
+                frame: 42,
+            },
+            Op::Signal('λ', Some(4)),
+            Op::Nop,
+        ],
+        reply: Some(-3),
+    }
+}
+
+/// Encode `val` with `opts`.
+pub fn encode<T: Serialize + ?Sized, O: Options>(val: &T, opts: O) -> Vec<u8> {
+    let mut buf = vec![0; serialized_size_with(val, opts).unwrap()];
+    assert_eq!(serialize_with(&mut buf, val, opts).unwrap(), buf.len());
+    buf
+}
+
+/// Encode `val` with the default options.
+pub fn encoded<T: Serialize + ?Sized>(val: &T) -> Vec<u8> {
+    encode(val, Config::new())
+}
+
+/// Encode `val` with `opts`, checking it decodes to the same value from all of the bytes.
+pub fn roundtrip<T, O>(val: &T, opts: O) -> Vec<u8>
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+    O: Options,
+{
+    let bytes = encode(val, opts);
+    let (new_val, len): (T, usize) = deserialize_with(&bytes, opts).unwrap();
+    assert_eq!(&new_val, val);
+    assert_eq!(len, bytes.len());
+    bytes
+}
@@ -0,0 +1,146 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+extern crate quickcheck;
+extern crate quickcheck_macros;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate ssmarshal;
+
+mod common;
+
+use quickcheck_macros::quickcheck;
+
+use ssmarshal::config::{BigEndian, NativeEndian, U16, U32, U8};
+use ssmarshal::{deserialize_with, serialize_with, Config, ErrorKind};
+
+use common::roundtrip;
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+struct Frame {
+    a: u16,
+    b: i32,
+    c: f64,
+    #[serde(with = "ssmarshal::word")]
+    len: usize,
+    #[serde(with = "ssmarshal::word")]
+    off: isize,
+    kind: Kind,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+enum Kind {
+    A,
+    B(u8),
+}
+
+fn frame() -> Frame {
+    Frame {
+        a: 0x0102,
+        b: -2,
+        c: 1.5,
+        len: 7,
+        off: -3,
+        kind: Kind::B(9),
+    }
+}
+
+#[test]
+fn default_matches_serialize() {
+    let mut buf = [0u8; 64];
+    let len = ssmarshal::serialize(&mut buf, &frame()).unwrap();
+    assert_eq!(roundtrip(&frame(), Config::new()), &buf[..len]);
+}
+
+#[test]
+fn big_endian() {
+    let bytes = roundtrip(&frame(), Config::new().with_endian::<BigEndian>());
+    assert_eq!(&bytes[..6], &[0x01, 0x02, 0xFF, 0xFF, 0xFF, 0xFE]);
+    assert_eq!(&bytes[6..14], &1.5f64.to_bits().to_be_bytes());
+}
+
+#[test]
+fn wide_integers() {
+    let mut buf = [0u8; 32];
+    let val = (0x0102u128, -2i128);
+    let opts = Config::new().with_endian::<BigEndian>();
+    assert_eq!(serialize_with(&mut buf, &val, opts).unwrap(), 32);
+    assert_eq!(&buf[14..18], &[0x01, 0x02, 0xFF, 0xFF]);
+    assert_eq!(&buf[30..], &[0xFF, 0xFE]);
+    let (new_val, _): ((u128, i128), usize) = deserialize_with(&buf, opts).unwrap();
+    assert_eq!(new_val, val);
+
+    assert_eq!(serialize_with(&mut buf, &val, Config::new()).unwrap(), 32);
+    assert_eq!(&buf[..2], &[0x02, 0x01]);
+    assert_eq!(&buf[16..18], &[0xFE, 0xFF]);
+}
+
+#[test]
+fn native_endian() {
+    let bytes = roundtrip(&frame(), Config::new().with_endian::<NativeEndian>());
+    assert_eq!(&bytes[..2], &0x0102u16.to_ne_bytes());
+}
+
+#[test]
+fn narrow_words_and_tags() {
+    let wide = roundtrip(&frame(), Config::new());
+    let narrow = roundtrip(&frame(), Config::new().with_usize::<U32>());
+    assert_eq!(wide.len(), narrow.len() + 8);
+    assert_eq!(&narrow[14..22], &[7, 0, 0, 0, 0xFD, 0xFF, 0xFF, 0xFF]);
+
+    let tagged = roundtrip(&frame(), Config::new().with_tag::<U16>());
+    assert_eq!(&tagged[tagged.len() - 3..], &[1, 0, 9]);
+}
+
+#[test]
+fn word_overflow() {
+    let mut buf = [0u8; 64];
+    let mut val = frame();
+    val.len = u32::MAX as usize + 1;
+    let err = serialize_with(&mut buf, &val, Config::new().with_usize::<U32>()).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::Overflow);
+    assert_eq!(err.offset(), Some(14));
+}
+
+/// A word token around something other than an integer doesn't narrow the next `u64`.
+#[test]
+fn stray_word_token() {
+    struct Stray;
+
+    impl serde::Serialize for Stray {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_newtype_struct(ssmarshal::word::USIZE_TOKEN, &1u8)
+        }
+    }
+
+    let mut buf = [0u8; 16];
+    let opts = Config::new().with_usize::<U32>();
+    assert_eq!(serialize_with(&mut buf, &(Stray, 2u64), opts).unwrap(), 9);
+    assert_eq!(&buf[..9], &[1, 2, 0, 0, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn short_lengths() {
+    let mut buf = [0u8; 512];
+    let opts = Config::new().with_len::<U8>();
+    assert_eq!(serialize_with(&mut buf, &[1u8, 2, 3][..], opts).unwrap(), 4);
+    assert_eq!(&buf[..4], &[3, 1, 2, 3]);
+    let err = serialize_with(&mut buf, &[0u8; 256][..], opts).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::Overflow);
+}
+
+type Mixed = (u16, i32, u64, f32, Option<i64>, char, i128);
+
+#[quickcheck]
+fn rt_big_endian(val: Mixed) -> bool {
+    let opts = Config::new().with_endian::<BigEndian>().with_tag::<U32>();
+    let mut buf = [0u8; 64];
+    let len = serialize_with(&mut buf, &val, opts).unwrap();
+    let (new_val, _): (Mixed, usize) = deserialize_with(&buf[..len], opts).unwrap();
+    val == new_val || val.3.is_nan()
+}
@@ -0,0 +1,90 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate ssmarshal;
+
+use serde::de::{DeserializeSeed, SeqAccess, Visitor};
+use ssmarshal::{Deserializer, Serializer};
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+struct Header {
+    kind: u8,
+    count: u16,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+enum Payload {
+    Empty,
+    Point(i32, i32),
+}
+
+#[test]
+fn back_to_back() {
+    let mut buf = [0u8; 32];
+    let mut ser = Serializer::new(&mut buf);
+    assert_eq!(ser.serialize(&Header { kind: 7, count: 2 }).unwrap(), 3);
+    assert_eq!(ser.serialize(&Payload::Point(-1, 1)).unwrap(), 9);
+    assert_eq!(ser.serialize(&Payload::Empty).unwrap(), 1);
+    assert_eq!(ser.position(), 13);
+    assert_eq!(ser.remaining(), 19);
+    assert_eq!(ser.into_inner().len(), 32);
+
+    let mut de = Deserializer::new(&buf[..13]);
+    let header: Header = de.deserialize().unwrap();
+    assert_eq!(header, Header { kind: 7, count: 2 });
+    assert_eq!(de.position(), 3);
+    for expected in &[Payload::Point(-1, 1), Payload::Empty] {
+        let payload: Payload = de.deserialize().unwrap();
+        assert_eq!(&payload, expected);
+    }
+    assert_eq!(de.remaining(), 0);
+}
+
+/// Reads a runtime-determined number of `u16`s and sums them.
+struct SumOf(usize);
+
+impl<'de> DeserializeSeed<'de> for SumOf {
+    type Value = u32;
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<u32, D::Error> {
+        struct SumVisitor;
+
+        impl<'de> Visitor<'de> for SumVisitor {
+            type Value = u32;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("u16s")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<u32, A::Error> {
+                let mut sum = 0;
+                while let Some(x) = seq.next_element::<u16>()? {
+                    sum += x as u32;
+                }
+                Ok(sum)
+            }
+        }
+
+        deserializer.deserialize_tuple(self.0, SumVisitor)
+    }
+}
+
+#[test]
+fn seed() {
+    let mut buf = [0u8; 16];
+    let mut ser = Serializer::new(&mut buf);
+    ser.serialize(&3u8).unwrap();
+    ser.serialize(&(100u16, 200u16, 300u16)).unwrap();
+
+    let mut de = Deserializer::new(&buf);
+    let count: u8 = de.deserialize().unwrap();
+    assert_eq!(de.deserialize_seed(SumOf(count as usize)).unwrap(), 600);
+    assert_eq!(de.position(), 7);
+}
@@ -0,0 +1,462 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+#![cfg(feature = "schema")]
+
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate ssmarshal;
+
+mod common;
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use serde::Deserialize;
+
+use ssmarshal::config::{BigEndian, SelfDescribing, U16, U32};
+use ssmarshal::dynamic::{self, Value};
+use ssmarshal::schema::{self, Schema};
+use ssmarshal::{Config, Deserializer, ErrorKind, Limit, Limits};
+
+use common::encode;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Command {
+    Stop,
+    Move(i32, i32),
+    Say(String),
+    Configure { speed: f32, name: char },
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Message {
+    id: u32,
+    reply: Option<u16>,
+    origin: (i8, bool),
+    mask: [u8; 2],
+    #[serde(with = "ssmarshal::word")]
+    len: usize,
+    commands: Vec<Command>,
+    tags: BTreeMap<u8, String>,
+}
+
+fn message() -> Message {
+    let mut tags = BTreeMap::new();
+    tags.insert(3, "three".to_string());
+    Message {
+        id: 7,
+        reply: Some(9),
+        origin: (-1, true),
+        mask: [1, 2],
+        len: 300,
+        commands: vec![
+            Command::Stop,
+            Command::Move(-2, 5),
+            Command::Say("hi".into()),
+            Command::Configure {
+                speed: 0.5,
+                name: 'λ',
+            },
+        ],
+        tags,
+    }
+}
+
+fn str(s: &str) -> Value {
+    Value::Str(s.into())
+}
+
+fn variant(name: &str, contents: Value) -> Value {
+    Value::Variant(name.into(), Box::new(contents))
+}
+
+fn message_value() -> Value {
+    Value::Struct(vec![
+        ("id".into(), Value::U32(7)),
+        ("reply".into(), Value::Option(Some(Box::new(Value::U16(9))))),
+        (
+            "origin".into(),
+            Value::Seq(vec![Value::I8(-1), Value::Bool(true)]),
+        ),
+        ("mask".into(), Value::Seq(vec![Value::U8(1), Value::U8(2)])),
+        ("len".into(), Value::U64(300)),
+        (
+            "commands".into(),
+            Value::Seq(vec![
+                variant("Stop", Value::Unit),
+                variant("Move", Value::Seq(vec![Value::I32(-2), Value::I32(5)])),
+                variant("Say", str("hi")),
+                variant(
+                    "Configure",
+                    Value::Struct(vec![
+                        ("speed".into(), Value::F32(0.5)),
+                        ("name".into(), Value::Char('λ')),
+                    ]),
+                ),
+            ]),
+        ),
+        (
+            "tags".into(),
+            Value::Map(vec![(Value::U8(3), str("three"))]),
+        ),
+    ])
+}
+
+/// Check that `val` survives being decoded and encoded again with the schema, byte for byte.
+fn roundtrip(schema: &Schema, bytes: &[u8]) -> Value {
+    let (val, len) = dynamic::decode(schema, bytes).unwrap();
+    assert_eq!(len, bytes.len());
+    let mut buf = [0u8; 1024];
+    let len = dynamic::encode(schema, &val, &mut buf).unwrap();
+    assert_eq!(&buf[..len], bytes);
+    val
+}
+
+#[test]
+fn decode_and_encode() {
+    let schema = schema::trace::<Message>().unwrap();
+    let bytes = encode(&message(), Config::new());
+    assert_eq!(roundtrip(&schema, &bytes), message_value());
+}
+
+#[test]
+fn options() {
+    let opts = Config::new()
+        .with_endian::<BigEndian>()
+        .with_usize::<U32>()
+        .with_len::<U16>()
+        .with_tag::<U16>();
+    let schema = schema::trace_with::<Message, _>(opts).unwrap();
+    let bytes = encode(&message(), opts);
+    assert_eq!(roundtrip(&schema, &bytes), message_value());
+}
+
+#[test]
+fn self_describing() {
+    let opts = Config::new().with_format::<SelfDescribing>();
+    let schema = schema::trace_with::<Message, _>(opts).unwrap();
+    let bytes = encode(&message(), opts);
+    assert_eq!(roundtrip(&schema, &bytes), message_value());
+}
+
+/// Decoding fails where and how deserializing the type would.
+#[cfg(not(feature = "debug-panics"))]
+#[test]
+fn same_errors() {
+    let schema = schema::trace::<Message>().unwrap();
+    let bytes = encode(&message(), Config::new());
+    let typed = |buf: &[u8]| {
+        let mut de = Deserializer::new(buf);
+        de.deserialize::<Message>().unwrap_err()
+    };
+
+    let short = &bytes[..bytes.len() - 3];
+    let err = dynamic::decode(&schema, short).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::EndOfStream);
+    assert_eq!(err.offset(), typed(short).offset());
+
+    // the `bool` in `origin`
+    let mut bad = bytes.clone();
+    bad[8] = 2;
+    let err = dynamic::decode(&schema, &bad).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::InvalidBool(2));
+    assert_eq!(err.offset(), Some(8));
+    assert_eq!(err.offset(), typed(&bad).offset());
+
+    // the discriminant of the first command
+    let mut bad = bytes.clone();
+    bad[27] = 9;
+    let err = dynamic::decode(&schema, &bad).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::UnknownVariant { index: 9 });
+    assert_eq!(err.offset(), Some(27));
+    assert_eq!(err.kind(), typed(&bad).kind());
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+enum List {
+    Cons(u8, Box<List>),
+    Nil,
+}
+
+#[test]
+fn limits() {
+    let schema = schema::trace::<List>().unwrap();
+    let bytes = [0, 1, 0, 2, 0, 3, 1];
+    let (val, _) = dynamic::decode(&schema, &bytes).unwrap();
+    assert_eq!(
+        val,
+        variant(
+            "Cons",
+            Value::Seq(vec![
+                Value::U8(1),
+                variant(
+                    "Cons",
+                    Value::Seq(vec![
+                        Value::U8(2),
+                        variant(
+                            "Cons",
+                            Value::Seq(vec![Value::U8(3), variant("Nil", Value::Unit)])
+                        )
+                    ])
+                ),
+            ])
+        )
+    );
+    let limits = Limits::new().with_max_depth(3);
+    dynamic::decode_limited(&schema, &bytes, limits).unwrap();
+    let limits = Limits::new().with_max_depth(2);
+    let err = dynamic::decode_limited(&schema, &bytes, limits).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::LimitExceeded(Limit::Depth));
+    assert!(ssmarshal::deserialize_limited::<List, _>(&bytes, Config::new(), limits).is_err());
+}
+
+/// A value read from a self-describing format doesn't know its exact types, but still encodes.
+#[test]
+fn loose_values() {
+    let schema = schema::trace::<Message>().unwrap();
+    let described = encode(&message(), Config::new().with_format::<SelfDescribing>());
+    let mut de =
+        Deserializer::with_options(&described, Config::new().with_format::<SelfDescribing>());
+    let loose = Value::deserialize(&mut de).unwrap();
+    assert!(matches!(loose, Value::Map(_)));
+
+    let mut buf = [0u8; 1024];
+    let len = dynamic::encode(&schema, &loose, &mut buf).unwrap();
+    assert_eq!(&buf[..len], &encode(&message(), Config::new())[..]);
+
+    let loose = Value::Map(vec![
+        (str("id"), Value::I64(7)),
+        (str("reply"), Value::Unit),
+        (
+            str("origin"),
+            Value::Seq(vec![Value::I64(-1), Value::Bool(true)]),
+        ),
+        (str("mask"), Value::Bytes(vec![1, 2])),
+        (str("len"), Value::U8(1)),
+        (
+            str("commands"),
+            Value::Seq(vec![str("Stop"), Value::Map(vec![(str("Say"), str("hi"))])]),
+        ),
+        (str("tags"), Value::Map(vec![(Value::U64(3), str("three"))])),
+    ]);
+    let len = dynamic::encode(&schema, &loose, &mut buf).unwrap();
+    let (typed, _): (Message, usize) =
+        ssmarshal::deserialize_limited(&buf[..len], Config::new(), Limits::new()).unwrap();
+    assert_eq!(typed.reply, None);
+    assert_eq!(typed.commands, [Command::Stop, Command::Say("hi".into())]);
+    assert_eq!(typed.tags[&3], "three");
+}
+
+#[cfg(not(feature = "debug-panics"))]
+#[test]
+fn encode_errors() {
+    let schema = schema::trace::<Command>().unwrap();
+    let mut buf = [0u8; 64];
+
+    let err = dynamic::encode(
+        &schema,
+        &variant("Move", Value::Seq(vec![Value::U64(1 << 40), Value::I32(0)])),
+        &mut buf,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.kind(),
+        &ErrorKind::Custom("1099511627776 is out of range for I32".into())
+    );
+    assert_eq!(err.offset(), Some(1));
+
+    let err = dynamic::encode(&schema, &str("Jump"), &mut buf).unwrap_err();
+    assert_eq!(
+        err.kind(),
+        &ErrorKind::Custom("`Command` has no variant `Jump`".into())
+    );
+
+    let err = dynamic::encode(
+        &schema,
+        &variant(
+            "Configure",
+            Value::Struct(vec![("speed".into(), Value::F32(1.0))]),
+        ),
+        &mut buf,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.kind(),
+        &ErrorKind::Custom("missing field `name`".into())
+    );
+
+    let err =
+        dynamic::encode(&schema, &variant("Say", str("too long")), &mut buf[..4]).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::EndOfStream);
+
+    // the typed serializer has no way to write a string as an integer either
+    let err = dynamic::encode(
+        &schema,
+        &variant("Move", Value::Seq(vec![str("5"), Value::I32(0)])),
+        &mut buf,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.kind(),
+        &ErrorKind::Custom("expected I32, found a string".into())
+    );
+}
+
+/// Values are encoded under the same rules as the typed serializer applies.
+#[test]
+fn same_rules() {
+    use ssmarshal::config::Canonical;
+
+    // a NaN's payload survives, rather than going through an `f64`
+    let nan = f32::from_bits(0x7FC0_0001);
+    let schema = schema::trace::<f32>().unwrap();
+    let bytes = dynamic::to_vec(&schema, &Value::F32(nan)).unwrap();
+    assert_eq!(bytes, encode(&nan, Config::new()));
+    let opts = Config::new().with_mode::<Canonical>();
+    let schema = schema::trace_with::<f32, _>(opts).unwrap();
+    let bytes = dynamic::to_vec(&schema, &Value::F32(nan)).unwrap();
+    assert_eq!(bytes, encode(&nan, opts));
+
+    let schema = schema::trace_with::<BTreeMap<u8, u8>, _>(opts).unwrap();
+    let map = Value::Map(vec![(Value::U8(1), Value::U8(2))]);
+    let err = dynamic::to_vec(&schema, &map).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::NonCanonicalMap);
+    let bytes = encode(&[(1u8, 2u8)], Config::new());
+    let err = dynamic::decode(&schema, &bytes).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::NonCanonicalMap);
+    assert_eq!(err.offset(), Some(0));
+}
+
+/// A `usize` is decoded at the configured width, whatever the host's own width is.
+#[test]
+fn wide_words() {
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Word(#[serde(with = "ssmarshal::word")] usize);
+
+    let schema = schema::trace::<Word>().unwrap();
+    let bytes = (1u64 << 40).to_le_bytes();
+    assert_eq!(roundtrip(&schema, &bytes), Value::U64(1 << 40));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn json() {
+    let val = Value::Struct(vec![
+        ("id".into(), Value::U32(7)),
+        ("reply".into(), Value::Option(None)),
+        (
+            "commands".into(),
+            Value::Seq(vec![
+                variant("Stop", Value::Unit),
+                variant("Move", Value::Seq(vec![Value::I32(-2), Value::I32(5)])),
+            ]),
+        ),
+        (
+            "tags".into(),
+            Value::Map(vec![(Value::U8(3), str("three"))]),
+        ),
+    ]);
+    assert_eq!(
+        val.to_json(false),
+        r#"{"id":7,"reply":null,"commands":["Stop",{"Move":[-2,5]}],"tags":{"3":"three"}}"#
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn from_json() {
+    let val = Value::from_json(
+        r#" {"id": 7, "reply": null, "origin": [-1, true], "mask": [1, 2], "len": 300,
+            "commands": ["Stop", {"Say": "h\u00e9\n\ud83d\ude00"},
+                {"Configure": {"speed": 0.5, "name": "λ"}}],
+            "tags": {"3": "three"}} "#,
+    )
+    .unwrap();
+    let (say, configure) = match &val {
+        Value::Map(entries) => match &entries[5].1 {
+            Value::Seq(commands) => (commands[1].clone(), commands[2].clone()),
+            _ => panic!(),
+        },
+        _ => panic!(),
+    };
+    assert_eq!(say, Value::Map(vec![(str("Say"), str("hé\n😀"))]));
+
+    let schema = schema::trace::<Message>().unwrap();
+    let bytes = dynamic::to_vec(&schema, &val).unwrap();
+    let (typed, _): (Message, usize) =
+        ssmarshal::deserialize_limited(&bytes, Config::new(), Limits::new()).unwrap();
+    assert_eq!(typed.commands[1], Command::Say("hé\n😀".into()));
+    assert_eq!(
+        configure,
+        Value::Map(vec![(
+            str("Configure"),
+            Value::Map(vec![
+                (str("speed"), Value::F64(0.5)),
+                (str("name"), str("λ"))
+            ])
+        )])
+    );
+
+    let err = Value::from_json(r#"{"id": [1,]}"#).unwrap_err();
+    assert_eq!(err.offset(), Some(10));
+    let err = Value::from_json("[1] 2").unwrap_err();
+    assert_eq!(err.offset(), Some(4));
+
+    for number in &["01", "1.", "-0e", "+1", ".5", "0x10"] {
+        assert!(Value::from_json(number).is_err(), "{}", number);
+    }
+    assert_eq!(Value::from_json("-0e1").unwrap(), Value::F64(-0.0));
+
+    let deep = |depth: usize| "[".repeat(depth) + &"]".repeat(depth);
+    Value::from_json(&deep(256)).unwrap();
+    let err = Value::from_json(&deep(257)).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::Custom("nested too deeply".into()));
+    assert_eq!(err.offset(), Some(256));
+}
+
+/// Numbers JSON can't hold in 64 bits, and floats it can't hold at all, survive a round trip.
+#[cfg(feature = "std")]
+#[test]
+fn json_numbers() {
+    let val = (
+        u128::MAX,
+        i128::MIN,
+        f32::INFINITY,
+        f64::NEG_INFINITY,
+        f64::NAN,
+    );
+    let schema = schema::trace::<(u128, i128, f32, f64, f64)>().unwrap();
+    let bytes = encode(&val, Config::new());
+    let (decoded, _) = dynamic::decode(&schema, &bytes).unwrap();
+    let json = decoded.to_json(false);
+    assert_eq!(
+        json,
+        concat!(
+            "[340282366920938463463374607431768211455,",
+            r#"-170141183460469231731687303715884105728,"inf","-inf","NaN"]"#
+        )
+    );
+    let read = Value::from_json(&json).unwrap();
+    assert_eq!(dynamic::to_vec(&schema, &read).unwrap(), bytes);
+}
+
+#[test]
+fn offsets() {
+    let schema = schema::trace::<Command>().unwrap();
+    let bytes = encode(&Command::Move(-2, 5), Config::new());
+    let (val, offsets) = dynamic::decode_with_offsets(&schema, &bytes).unwrap();
+    assert_eq!(
+        val,
+        variant("Move", Value::Seq(vec![Value::I32(-2), Value::I32(5)]))
+    );
+    let expected: [Range<usize>; 4] = [0..9, 1..9, 1..5, 5..9];
+    assert_eq!(offsets, expected);
+}
@@ -0,0 +1,78 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate ssmarshal;
+
+mod common;
+
+use ssmarshal::{deserialize, Error, ErrorKind, Segment};
+
+use common::{encoded, message, Message};
+
+fn corrupt(offset: usize, byte: u8) -> Error {
+    let mut buf = encoded(&message());
+    buf[offset] = byte;
+    deserialize::<Message>(&buf).unwrap_err()
+}
+
+#[test]
+fn invalid_bool() {
+    let err = corrupt(2, 2);
+    assert_eq!(err.kind(), &ErrorKind::InvalidBool(2));
+    assert_eq!(err.offset(), Some(2));
+    let path: Vec<Segment> = err.path().iter().collect();
+    assert_eq!(path, [Segment::Field("header"), Segment::Field("urgent")]);
+    assert_eq!(
+        err.to_string(),
+        "invalid bool 0x02 at byte 2 in .header.urgent"
+    );
+}
+
+#[test]
+fn invalid_option_tag() {
+    let err = corrupt(19, 5);
+    assert_eq!(err.kind(), &ErrorKind::InvalidOptionTag(5));
+    assert_eq!(err.offset(), Some(19));
+    assert_eq!(err.path().to_string(), ".ops[1]::Signal[1]");
+}
+
+#[test]
+fn invalid_char() {
+    let err = corrupt(17, 0xFF);
+    assert_eq!(err.kind(), &ErrorKind::InvalidChar);
+    assert_eq!(err.offset(), Some(17));
+    assert_eq!(err.path().to_string(), ".ops[1]::Signal[0]");
+}
+
+#[test]
+fn unknown_variant() {
+    let err = corrupt(16, 3);
+    assert_eq!(err.kind(), &ErrorKind::UnknownVariant { index: 3 });
+    assert_eq!(err.offset(), Some(16));
+    assert_eq!(err.path().to_string(), ".ops[1]");
+}
+
+#[test]
+fn truncated_path() {
+    type Deep = ((((((bool,),),),),),);
+    let err = deserialize::<Deep>(&[9]).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::InvalidBool(9));
+    assert!(err.path().is_truncated());
+    assert_eq!(err.path().len(), ssmarshal::MAX_DEPTH);
+    assert_eq!(err.path().to_string(), "...[0][0][0][0]");
+}
+
+#[test]
+fn application_error() {
+    let err = Error::from(ErrorKind::ApplicationError("nope"));
+    assert_eq!(err.offset(), None);
+    assert!(err.path().is_empty());
+    assert_eq!(err.to_string(), "application error: nope");
+}
@@ -0,0 +1,76 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+// Without `debug-panics`, bad input must come back as an error even in debug builds.
+#![cfg(not(feature = "debug-panics"))]
+
+extern crate quickcheck;
+extern crate quickcheck_macros;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate ssmarshal;
+
+mod common;
+
+use quickcheck_macros::quickcheck;
+use serde::de::IgnoredAny;
+
+use ssmarshal::{deserialize, deserialize_borrowed, serialize, ErrorKind, Serializer};
+
+use common::{message, Message, Op};
+
+/// A message along with a string borrowed from the buffer.
+type Request<'a> = (Message, &'a str);
+
+fn request() -> Request<'static> {
+    (message(), "robot")
+}
+
+#[test]
+fn truncated() {
+    let mut buf = [0u8; 128];
+    let len = Serializer::new(&mut buf).serialize(&request()).unwrap();
+    for end in 0..len {
+        let err = deserialize_borrowed::<Request>(&buf[..end]).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::EndOfStream, "{}", end);
+        assert!(err.offset().unwrap() <= end);
+    }
+    assert_eq!(
+        deserialize_borrowed::<Request>(&buf[..len]).unwrap().0,
+        request()
+    );
+}
+
+#[test]
+fn short_output() {
+    let mut buf = [0u8; 4];
+    let err = serialize(&mut buf, &(1u16, 2u32)).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::EndOfStream);
+    assert_eq!(err.offset(), Some(2));
+}
+
+#[test]
+fn unsupported() {
+    let err = deserialize::<IgnoredAny>(&[0]).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::NotSupported);
+}
+
+#[quickcheck]
+fn arbitrary_bytes(buf: Vec<u8>) -> bool {
+    let _ = deserialize_borrowed::<Request>(&buf);
+    let _ = deserialize::<(Op, Option<bool>, [char; 2], f64)>(&buf);
+    true
+}
+
+#[cfg(feature = "alloc")]
+#[quickcheck]
+fn arbitrary_bytes_alloc(buf: Vec<u8>) -> bool {
+    let _ = deserialize::<Vec<String>>(&buf);
+    let _ = deserialize::<String>(&buf);
+    true
+}
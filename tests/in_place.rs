@@ -0,0 +1,151 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate ssmarshal;
+
+mod common;
+
+use std::mem::MaybeUninit;
+
+use ssmarshal::{
+    deserialize, deserialize_into, deserialize_uninit, serialize, Deserializer, Zeroable,
+};
+
+use common::{encoded, message, Op};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Table {
+    entries: [[u32; 32]; 32],
+    count: u16,
+}
+
+unsafe impl Zeroable for Table {}
+
+fn table() -> Table {
+    let mut entries = [[0; 32]; 32];
+    for (i, row) in entries.iter_mut().enumerate() {
+        for (j, e) in row.iter_mut().enumerate() {
+            *e = (i * 32 + j) as u32;
+        }
+    }
+    Table {
+        entries,
+        count: 1024,
+    }
+}
+
+#[test]
+fn into_existing() {
+    let buf = encoded(&table());
+
+    let mut place = Box::new(Table {
+        entries: [[0xFFFF_FFFF; 32]; 32],
+        count: 0,
+    });
+    assert_eq!(deserialize_into(&mut *place, &buf).unwrap(), buf.len());
+    assert_eq!(place.entries, table().entries);
+    assert_eq!(place.count, 1024);
+}
+
+#[test]
+fn same_as_deserialize() {
+    let buf = encoded(&message());
+
+    let mut place = message();
+    place.header.urgent = false;
+    place.ops = [
+        Op::Nop,
+        Op::Signal('a', None),
+        Op::Map { vaddr: 1, frame: 2 },
+    ];
+    place.reply = None;
+    assert_eq!(deserialize_into(&mut place, &buf).unwrap(), buf.len());
+    assert_eq!((place, buf.len()), deserialize(&buf).unwrap());
+}
+
+#[test]
+fn uninit() {
+    let mut buf = [0; 16];
+    let len = serialize(&mut buf, &(5u32, -1i16)).unwrap();
+
+    let mut slot = MaybeUninit::uninit();
+    let (val, used) = deserialize_uninit::<(u32, i16)>(&mut slot, &buf).unwrap();
+    assert_eq!(used, len);
+    assert_eq!(*val, (5, -1));
+    *val = (6, 0);
+    assert_eq!(unsafe { slot.assume_init() }, (6, 0));
+}
+
+#[test]
+fn uninit_large() {
+    let buf = encoded(&table());
+
+    let mut slot = Box::new(MaybeUninit::<Table>::uninit());
+    let (val, used) = deserialize_uninit(&mut *slot, &buf).unwrap();
+    assert_eq!(used, buf.len());
+    assert_eq!(*val, table());
+}
+
+#[test]
+fn several_values() {
+    let mut buf = [0; 16];
+    let mut ser = ssmarshal::Serializer::new(&mut buf);
+    ser.serialize(&[1u8, 2, 3]).unwrap();
+    ser.serialize(&[4u8, 5, 6]).unwrap();
+
+    let mut de = Deserializer::new(&buf);
+    let mut place = [0u8; 3];
+    de.deserialize_in_place(&mut place).unwrap();
+    assert_eq!(place, [1, 2, 3]);
+    de.deserialize_in_place(&mut place).unwrap();
+    assert_eq!(place, [4, 5, 6]);
+    assert_eq!(de.position(), 6);
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Batch {
+    id: u8,
+    items: Vec<u32>,
+}
+
+// a `Vec` decoded in place keeps its allocation, which shows the derived struct was too
+#[cfg(feature = "alloc")]
+#[test]
+fn reuses_allocations() {
+    let mut buf = [0; 64];
+    let batch = Batch {
+        id: 2,
+        items: vec![1, 2, 3],
+    };
+    let len = serialize(&mut buf, &batch).unwrap();
+
+    let mut place = Batch {
+        id: 1,
+        items: Vec::with_capacity(16),
+    };
+    place.items.extend_from_slice(&[9, 9, 9, 9, 9]);
+    let ptr = place.items.as_ptr();
+    assert_eq!(deserialize_into(&mut place, &buf).unwrap(), len);
+    assert_eq!(place, batch);
+    assert_eq!(place.items.as_ptr(), ptr);
+}
+
+#[cfg(not(feature = "debug-panics"))]
+#[test]
+fn short_buffer() {
+    let mut place = [0u32; 4];
+    let err = deserialize_into(&mut place, &[1, 0, 0, 0, 2, 0]).unwrap_err();
+    assert_eq!(err.kind(), &ssmarshal::ErrorKind::EndOfStream);
+    assert_eq!(err.offset(), Some(4));
+
+    let mut slot = MaybeUninit::<[u32; 4]>::uninit();
+    assert!(deserialize_uninit(&mut slot, &[1, 0]).is_err());
+}
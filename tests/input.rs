@@ -0,0 +1,138 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+extern crate quickcheck;
+extern crate quickcheck_macros;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate ssmarshal;
+
+mod common;
+
+use ssmarshal::input::{IterInput, SegmentedInput};
+use ssmarshal::{serialize, DefaultOptions, Deserializer, Input, SliceInput};
+
+use common::{encoded, message, Message};
+
+fn read<'de, R: Input<'de>>(input: R) -> (Message, usize) {
+    let mut de = Deserializer::from_input(input, DefaultOptions::new());
+    let val = de.deserialize().unwrap();
+    (val, de.position())
+}
+
+#[test]
+fn slice() {
+    let buf = encoded(&message());
+    assert_eq!(read(SliceInput::new(&buf)), (message(), buf.len()));
+}
+
+#[test]
+fn wrapped_ring() {
+    // every way a ring buffer could have wrapped around the message
+    let buf = encoded(&message());
+    for split in 0..=buf.len() {
+        let (tail, head) = buf.split_at(split);
+        let segments = [tail, head];
+        let mut input = SegmentedInput::new(&segments);
+        assert_eq!(read(&mut input), (message(), buf.len()), "{}", split);
+        assert_eq!(input.remaining(), 0);
+    }
+}
+
+#[test]
+fn byte_iterator() {
+    let buf = encoded(&message());
+    let mut bytes = buf.iter().cloned().chain(Some(0xAA));
+    assert_eq!(read(IterInput::new(&mut bytes)), (message(), buf.len()));
+    assert_eq!(bytes.next(), Some(0xAA));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn reader() {
+    use ssmarshal::input::IoInput;
+    use std::io::Read;
+
+    let buf = encoded(&message());
+    let reader = (&buf[..5]).chain(&buf[5..]);
+    assert_eq!(read(IoInput::new(reader)), (message(), buf.len()));
+}
+
+#[test]
+fn borrowed_within_segment() {
+    let mut buf = [0u8; 32];
+    let len = serialize(&mut buf, &(1u8, "abc")).unwrap();
+    let segments = [&buf[..1], &buf[1..len]];
+    let mut de = Deserializer::from_input(SegmentedInput::new(&segments), DefaultOptions::new());
+    let (a, s): (u8, &str) = de.deserialize().unwrap();
+    assert_eq!((a, s), (1, "abc"));
+    assert_eq!(s.as_ptr(), buf[9..].as_ptr());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn copied_across_segments() {
+    let val = (String::from("split me"), vec![1u8, 2, 3]);
+    let buf = ssmarshal::to_vec(&val).unwrap();
+    for split in 0..=buf.len() {
+        let segments = [&buf[..split], &buf[split..]];
+        let mut de =
+            Deserializer::from_input(SegmentedInput::new(&segments), DefaultOptions::new());
+        let new_val: (String, Vec<u8>) = de.deserialize().unwrap();
+        assert_eq!(new_val, val);
+        let mut de = Deserializer::from_input(IterInput::new(buf.clone()), DefaultOptions::new());
+        assert_eq!(de.deserialize::<(String, Vec<u8>)>().unwrap(), val);
+    }
+}
+
+#[cfg(not(feature = "debug-panics"))]
+#[test]
+fn truncated() {
+    use ssmarshal::ErrorKind;
+
+    let buf = encoded(&message());
+    for end in 0..buf.len() {
+        let mut de = Deserializer::from_input(
+            IterInput::new(buf[..end].iter().cloned()),
+            DefaultOptions::new(),
+        );
+        let err = de.deserialize::<Message>().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::EndOfStream);
+    }
+}
+
+/// A reader which runs out partway through a value fails where that read started, as a slice
+/// does, but still counts the bytes it did give.
+#[cfg(all(feature = "std", not(feature = "debug-panics")))]
+#[test]
+fn truncated_reader() {
+    use ssmarshal::input::IoInput;
+    use ssmarshal::ErrorKind;
+    use std::io::Read;
+
+    let buf = encoded(&message());
+    for end in 0..buf.len() {
+        let reader = (&buf[..end / 2]).chain(&buf[end / 2..end]);
+        let mut de = Deserializer::from_input(IoInput::new(reader), DefaultOptions::new());
+        let err = de.deserialize::<Message>().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::EndOfStream);
+        let slice_err = ssmarshal::deserialize::<Message>(&buf[..end]).unwrap_err();
+        assert_eq!(err.offset(), slice_err.offset());
+        assert_eq!(de.position(), end);
+    }
+}
+
+#[cfg(not(feature = "debug-panics"))]
+#[quickcheck_macros::quickcheck]
+fn same_as_slice(buf: Vec<u8>, split: usize) -> bool {
+    let split = split % (buf.len() + 1);
+    let segments = [&buf[..split], &buf[split..]];
+    let from_slice = Deserializer::new(&buf).deserialize::<(u16, Option<char>, [i32; 2])>();
+    let mut de = Deserializer::from_input(SegmentedInput::new(&segments), DefaultOptions::new());
+    from_slice.ok() == de.deserialize().ok()
+}
@@ -0,0 +1,125 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+#![cfg(feature = "std")]
+
+extern crate quickcheck;
+extern crate quickcheck_macros;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate ssmarshal;
+
+use std::io::{self, Cursor, Read, Write};
+
+use quickcheck_macros::quickcheck;
+
+use ssmarshal::{deserialize_from, serialize, serialize_into, ErrorKind};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Request {
+    Open { path: String, write: bool },
+    Read(u32, u64),
+    Close(u32),
+}
+
+/// A reader or writer which always fails.
+struct Broken;
+
+impl Read for Broken {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::BrokenPipe.into())
+    }
+}
+
+impl Write for Broken {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::BrokenPipe.into())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn back_to_back() {
+    let requests = [
+        Request::Open {
+            path: "/dev/null".into(),
+            write: true,
+        },
+        Request::Read(3, 512),
+        Request::Close(3),
+    ];
+    let mut pipe = Vec::new();
+    for req in &requests {
+        serialize_into(&mut pipe, req).unwrap();
+    }
+
+    let mut reader = Cursor::new(pipe);
+    for req in &requests {
+        let (new_req, _) = deserialize_from::<_, Request>(&mut reader).unwrap();
+        assert_eq!(&new_req, req);
+    }
+    assert_eq!(reader.position() as usize, reader.get_ref().len());
+}
+
+#[test]
+fn same_bytes_as_serialize() {
+    let val = (7u16, Some('x'), [1i32, -1]);
+    let mut buf = [0u8; 32];
+    let len = serialize(&mut buf, &val).unwrap();
+    let mut out = Vec::new();
+    assert_eq!(serialize_into(&mut out, &val).unwrap(), len);
+    assert_eq!(out, &buf[..len]);
+    assert_eq!(deserialize_from(&out[..]).unwrap(), (val, len));
+}
+
+#[cfg(not(feature = "debug-panics"))]
+#[test]
+fn end_of_stream() {
+    let err = deserialize_from::<_, (u32, u32)>(&[1, 0, 0, 0, 2][..]).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::EndOfStream);
+    assert_eq!(err.offset(), Some(4));
+}
+
+#[test]
+fn io_errors() {
+    let err = deserialize_from::<_, u32>(Broken).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::Io(io::ErrorKind::BrokenPipe));
+    assert_eq!(err.offset(), Some(0));
+    let err = serialize_into(Broken, &1u8).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::Io(io::ErrorKind::BrokenPipe));
+    assert!(err.to_string().starts_with("I/O error: "));
+}
+
+#[cfg(unix)]
+#[test]
+fn socket() {
+    use std::os::unix::net::UnixStream;
+    use std::thread;
+
+    let (mut a, mut b) = UnixStream::pair().unwrap();
+    let sender = thread::spawn(move || {
+        for i in 0..10 {
+            serialize_into(&mut a, &Request::Read(i, i as u64 * 100)).unwrap();
+        }
+    });
+    for i in 0..10 {
+        let (req, _) = deserialize_from::<_, Request>(&mut b).unwrap();
+        assert_eq!(req, Request::Read(i, i as u64 * 100));
+    }
+    sender.join().unwrap();
+}
+
+#[quickcheck]
+fn rt_io(val: Vec<(String, Option<i64>)>) -> bool {
+    let mut out = Vec::new();
+    let len = serialize_into(&mut out, &val).unwrap();
+    len == out.len() && deserialize_from(&out[..]).unwrap() == (val, len)
+}
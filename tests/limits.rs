@@ -0,0 +1,185 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+#![cfg(feature = "alloc")]
+
+extern crate heapless;
+extern crate quickcheck;
+extern crate quickcheck_macros;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate ssmarshal;
+
+use quickcheck_macros::quickcheck;
+
+use ssmarshal::{
+    deserialize_borrowed, deserialize_limited, to_vec, Config, Deserializer, ErrorKind, Limit,
+    Limits,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum List {
+    Nil,
+    Cons(u8, Box<List>),
+}
+
+fn list(len: usize) -> List {
+    (0..len).fold(List::Nil, |tail, i| List::Cons(i as u8, Box::new(tail)))
+}
+
+#[test]
+fn max_len() {
+    let buf = to_vec(&vec![7u16; 5]).unwrap();
+    let limits = Limits::new().with_max_len(5);
+    let (val, _) = deserialize_limited::<Vec<u16>, _>(&buf, Config::new(), limits).unwrap();
+    assert_eq!(val, [7; 5]);
+
+    let limits = Limits::new().with_max_len(4);
+    let err = deserialize_limited::<Vec<u16>, _>(&buf, Config::new(), limits).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::LimitExceeded(Limit::Len));
+    assert_eq!(err.offset(), Some(0));
+    assert_eq!(err.to_string(), "length limit exceeded at byte 0");
+}
+
+#[test]
+fn huge_len() {
+    // a length prefix far beyond the buffer is rejected before anything is allocated
+    let buf = [0xFF; 8];
+    let limits = Limits::new().with_max_len(1 << 16);
+    let err = deserialize_limited::<Vec<u8>, _>(&buf, Config::new(), limits).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::LimitExceeded(Limit::Len));
+}
+
+#[test]
+fn strings_and_bytes() {
+    let buf = to_vec(&("hello", "hi")).unwrap();
+    let limits = Limits::new().with_max_len(4);
+    let err = deserialize_limited::<(String, String), _>(&buf, Config::new(), limits).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::LimitExceeded(Limit::Len));
+    assert_eq!(err.offset(), Some(0));
+
+    // borrowed slices count towards the elements too
+    let limits = Limits::new().with_max_elements(6);
+    let mut de = Deserializer::new(&buf).with_limits(limits);
+    let err = de.deserialize::<(&str, &[u8])>().unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::LimitExceeded(Limit::Elements));
+    assert_eq!(err.offset(), Some(13));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn endless_stream() {
+    use ssmarshal::input::IoInput;
+    use ssmarshal::DefaultOptions;
+
+    // a peer claiming a huge string and then sending bytes forever
+    let prefix: &[u8] = &[0xFF; 8];
+    let reader = std::io::Read::chain(prefix, std::io::repeat(b'a'));
+    let mut de = Deserializer::from_input(IoInput::new(reader), DefaultOptions::new())
+        .with_limits(Limits::new().with_max_len(1 << 10));
+    let err = de.deserialize::<String>().unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::LimitExceeded(Limit::Len));
+}
+
+#[test]
+fn bounded_collections() {
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Packet {
+        #[serde(with = "ssmarshal::bounded::heapless_vec")]
+        payload: heapless::Vec<u8, 16>,
+    }
+
+    let payload = heapless::Vec::from_slice(&[1, 2, 3, 4, 5]).unwrap();
+    let buf = to_vec(&Packet { payload }).unwrap();
+    let limits = Limits::new().with_max_len(4);
+    let err = deserialize_limited::<Packet, _>(&buf, Config::new(), limits).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::LimitExceeded(Limit::Len));
+    assert_eq!(err.offset(), Some(0));
+    let limits = Limits::new().with_max_elements(5);
+    assert!(deserialize_limited::<Packet, _>(&buf, Config::new(), limits).is_ok());
+}
+
+#[test]
+fn max_elements() {
+    let val = vec![vec![1u8, 2], vec![3, 4, 5]];
+    let buf = to_vec(&val).unwrap();
+    let limits = Limits::new().with_max_elements(7);
+    let (new_val, _) = deserialize_limited::<Vec<Vec<u8>>, _>(&buf, Config::new(), limits).unwrap();
+    assert_eq!(new_val, val);
+
+    let limits = Limits::new().with_max_elements(6);
+    let err = deserialize_limited::<Vec<Vec<u8>>, _>(&buf, Config::new(), limits).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::LimitExceeded(Limit::Elements));
+    assert_eq!(err.offset(), Some(18));
+    assert_eq!(err.path().to_string(), "[1]");
+}
+
+#[test]
+fn budget_is_per_value() {
+    let buf = to_vec(&(vec![1u8, 2, 3], vec![4u8, 5, 6])).unwrap();
+    let limits = Limits::new().with_max_elements(3);
+    let mut de = Deserializer::new(&buf).with_limits(limits);
+    assert_eq!(de.deserialize::<Vec<u8>>().unwrap(), [1, 2, 3]);
+    assert_eq!(de.deserialize::<Vec<u8>>().unwrap(), [4, 5, 6]);
+    assert_eq!(de.limits(), limits);
+}
+
+#[test]
+fn max_depth() {
+    let buf = to_vec(&list(10)).unwrap();
+    // each Cons is a tuple variant holding a Box
+    let limits = Limits::new().with_max_depth(10);
+    let (val, _) = deserialize_limited::<List, _>(&buf, Config::new(), limits).unwrap();
+    assert_eq!(val, list(10));
+
+    let limits = Limits::new().with_max_depth(9);
+    let err = deserialize_limited::<List, _>(&buf, Config::new(), limits).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::LimitExceeded(Limit::Depth));
+    assert_eq!(err.offset(), Some(19));
+    assert!(err.path().is_truncated());
+
+    let err = deserialize_limited::<Option<Option<u8>>, _>(
+        &[1, 1, 0],
+        Config::new(),
+        Limits::new().with_max_depth(1),
+    )
+    .unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::LimitExceeded(Limit::Depth));
+    assert_eq!(err.offset(), Some(2));
+}
+
+#[test]
+fn deep_buffer() {
+    // without a limit, this many levels would overflow the stack
+    let mut buf = vec![];
+    for _ in 0..1_000_000 {
+        buf.extend_from_slice(&[1, 0]);
+    }
+    let limits = Limits::new().with_max_depth(128);
+    let err = deserialize_limited::<List, _>(&buf, Config::new(), limits).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::LimitExceeded(Limit::Depth));
+    assert_eq!(err.offset(), Some(257));
+}
+
+#[quickcheck]
+fn unlimited(val: Vec<Vec<u32>>) -> bool {
+    let buf = to_vec(&val).unwrap();
+    let limited = deserialize_limited::<Vec<Vec<u32>>, _>(&buf, Config::new(), Limits::new());
+    limited.unwrap() == deserialize_borrowed(&buf).unwrap()
+}
+
+#[cfg(not(feature = "debug-panics"))]
+#[quickcheck]
+fn arbitrary_bytes(buf: Vec<u8>) -> bool {
+    let limits = Limits::new()
+        .with_max_len(16)
+        .with_max_depth(8)
+        .with_max_elements(64);
+    let _ = deserialize_limited::<(List, Vec<Vec<u8>>), _>(&buf, Config::new(), limits);
+    true
+}
@@ -0,0 +1,78 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+extern crate quickcheck;
+extern crate quickcheck_macros;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate ssmarshal;
+
+use std::mem::align_of;
+
+use quickcheck_macros::quickcheck;
+
+use ssmarshal::{ErrorKind, Marshaled, MaxSize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, MaxSize)]
+enum Msg {
+    Ping,
+    Write { addr: u32, data: [u8; 4] },
+    Ack(Option<u16>),
+}
+
+type Frame = Marshaled<Msg, { Msg::MAX_SIZE }>;
+
+#[test]
+fn encode_decode() {
+    let msg = Msg::Write {
+        addr: 0x1000,
+        data: [1, 2, 3, 4],
+    };
+    let frame = Frame::encode(&msg).unwrap();
+    assert_eq!(frame.len(), 9);
+    assert_eq!(frame.as_bytes(), &[1, 0, 0x10, 0, 0, 1, 2, 3, 4]);
+    assert_eq!(frame.decode().unwrap(), msg);
+
+    let ping = Frame::encode(&Msg::Ping).unwrap();
+    assert_eq!(ping.as_bytes(), &[0]);
+    assert_ne!(ping, frame);
+    assert_eq!(ping.clone(), ping);
+}
+
+#[test]
+fn from_bytes() {
+    let frame = Frame::from_bytes(&[2, 1, 7, 0]).unwrap();
+    assert_eq!(frame.decode().unwrap(), Msg::Ack(Some(7)));
+
+    let err = Frame::from_bytes(&[0; 10]).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::CapacityExceeded);
+
+    let err = Frame::from_bytes(&[0, 0]).unwrap().decode().unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::TrailingBytes);
+    assert_eq!(err.offset(), Some(1));
+}
+
+#[test]
+fn aligned() {
+    type Aligned = Marshaled<Msg, 16, u64>;
+    assert_eq!(align_of::<Aligned>(), 8);
+    let frame = Aligned::encode(&Msg::Ack(None)).unwrap();
+    assert_eq!(frame.as_bytes().as_ptr() as usize % 8, 0);
+    assert_eq!(frame.decode().unwrap(), Msg::Ack(None));
+}
+
+#[quickcheck]
+fn rt_marshaled(addr: u32, data: (u8, u8, u8, u8), ack: Option<u16>) -> bool {
+    let write = Msg::Write {
+        addr,
+        data: [data.0, data.1, data.2, data.3],
+    };
+    let ack = Msg::Ack(ack);
+    Frame::encode(&write).unwrap().decode().unwrap() == write
+        && Frame::encode(&ack).unwrap().decode().unwrap() == ack
+}
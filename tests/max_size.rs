@@ -0,0 +1,110 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate quickcheck;
+extern crate quickcheck_macros;
+extern crate rand;
+
+extern crate ssmarshal;
+
+use std::num::NonZeroU32;
+
+use quickcheck_macros::quickcheck;
+use rand::Rng;
+
+use ssmarshal::{serialize, MaxSize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize, MaxSize)]
+struct Header {
+    kind: u8,
+    len: u16,
+    flags: (bool, bool),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize, MaxSize)]
+struct Unit;
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize, MaxSize)]
+struct Wrapper<T>(T, #[serde(skip)] u64);
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize, MaxSize)]
+enum Msg {
+    Ping,
+    Data(Header, [u8; 13]),
+    Move { x: i32, y: i32, z: Option<f64> },
+    Nested(Wrapper<char>),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize, MaxSize)]
+enum Never {}
+
+impl quickcheck::Arbitrary for Msg {
+    fn arbitrary<G: quickcheck::Gen>(g: &mut G) -> Msg {
+        match g.gen_range(0, 4) {
+            0 => Msg::Ping,
+            1 => Msg::Data(
+                Header {
+                    kind: g.gen(),
+                    len: g.gen(),
+                    flags: g.gen(),
+                },
+                g.gen(),
+            ),
+            2 => Msg::Move {
+                x: g.gen(),
+                y: g.gen(),
+                z: quickcheck::Arbitrary::arbitrary(g),
+            },
+            3 => Msg::Nested(Wrapper(g.gen(), 0)),
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[test]
+fn primitives() {
+    assert_eq!(<()>::MAX_SIZE, 0);
+    assert_eq!(bool::MAX_SIZE, 1);
+    assert_eq!(u16::MAX_SIZE, 2);
+    assert_eq!(usize::MAX_SIZE, 8);
+    assert_eq!(char::MAX_SIZE, 4);
+    assert_eq!(<[u32; 5]>::MAX_SIZE, 20);
+    assert_eq!(<(u8, i64, f32)>::MAX_SIZE, 13);
+    assert_eq!(<Option<NonZeroU32>>::MAX_SIZE, 5);
+}
+
+#[test]
+fn derived() {
+    assert_eq!(Header::MAX_SIZE, 5);
+    assert_eq!(Unit::MAX_SIZE, 0);
+    assert_eq!(<Wrapper<u32>>::MAX_SIZE, 4);
+    assert_eq!(Never::MAX_SIZE, 0);
+    // tag + Data(Header, [u8; 13])
+    assert_eq!(Msg::MAX_SIZE, 1 + 5 + 13);
+}
+
+#[test]
+fn usable_as_array_length() {
+    let mut buf = [0u8; Msg::MAX_SIZE];
+    let msg = Msg::Data(
+        Header {
+            kind: 1,
+            len: 2,
+            flags: (true, false),
+        },
+        [0xFF; 13],
+    );
+    assert_eq!(serialize(&mut buf, &msg).unwrap(), Msg::MAX_SIZE);
+}
+
+#[quickcheck]
+fn bounds_encoding(msg: Msg) -> bool {
+    let mut buf = [0u8; Msg::MAX_SIZE];
+    serialize(&mut buf, &msg).unwrap() <= Msg::MAX_SIZE
+}
@@ -0,0 +1,91 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+extern crate quickcheck;
+extern crate quickcheck_macros;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate ssmarshal;
+
+use std::mem::size_of;
+use std::num::{NonZeroI16, NonZeroU32, NonZeroU8, NonZeroUsize};
+
+use quickcheck_macros::quickcheck;
+
+use ssmarshal::niche::Niche;
+use ssmarshal::{deserialize, serialize, MaxSize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize, MaxSize)]
+#[repr(C)]
+struct Handles {
+    parent: Niche<NonZeroU32>,
+    #[serde(with = "ssmarshal::niche")]
+    child: Option<NonZeroU32>,
+    flags: Niche<NonZeroU8>,
+    delta: Niche<NonZeroI16>,
+}
+
+#[test]
+fn none_is_zero() {
+    let handles = Handles {
+        parent: Niche(None),
+        child: None,
+        flags: Niche::default(),
+        delta: Niche(None),
+    };
+    let mut buf = [0xFFu8; 16];
+    let len = serialize(&mut buf, &handles).unwrap();
+    assert_eq!(len, 11);
+    assert_eq!(buf[..len], [0; 11]);
+    assert_eq!(deserialize::<Handles>(&buf).unwrap(), (handles, 11));
+}
+
+#[test]
+fn some_is_value() {
+    let handles = Handles {
+        parent: NonZeroU32::new(0x0102_0304).into(),
+        child: NonZeroU32::new(5),
+        flags: NonZeroU8::new(6).into(),
+        delta: NonZeroI16::new(-1).into(),
+    };
+    let mut buf = [0u8; 16];
+    let len = serialize(&mut buf, &handles).unwrap();
+    assert_eq!(buf[..len], [4, 3, 2, 1, 5, 0, 0, 0, 6, 0xFF, 0xFF]);
+    assert_eq!(deserialize::<Handles>(&buf).unwrap(), (handles, 11));
+}
+
+#[test]
+fn size_invariant() {
+    assert_eq!(size_of::<Niche<NonZeroU32>>(), 4);
+    assert_eq!(Niche::<NonZeroU32>::MAX_SIZE, 4);
+    assert_eq!(Niche::<NonZeroUsize>::MAX_SIZE, 8);
+    assert_eq!(Option::<NonZeroU32>::MAX_SIZE, 5);
+    // derive can't see through `with`, so `child` is counted with its tag byte
+    assert_eq!(Handles::MAX_SIZE, 12);
+    assert!(Handles::MAX_SIZE <= size_of::<Handles>());
+}
+
+#[quickcheck]
+fn rt_niche(parent: u32, child: u32, flags: u8, delta: i16) -> bool {
+    let handles = Handles {
+        parent: NonZeroU32::new(parent).into(),
+        child: NonZeroU32::new(child),
+        flags: NonZeroU8::new(flags).into(),
+        delta: NonZeroI16::new(delta).into(),
+    };
+    let mut buf = [0u8; Handles::MAX_SIZE];
+    let len = serialize(&mut buf, &handles).unwrap();
+    len <= size_of::<Handles>() && deserialize::<Handles>(&buf).unwrap() == (handles, len)
+}
+
+#[cfg(not(feature = "debug-panics"))]
+#[quickcheck]
+fn any_bytes_decode(buf: Vec<u8>) -> bool {
+    // zero is None and everything else is Some, so only a short buffer is an error
+    deserialize::<Handles>(&buf).is_ok() == (buf.len() >= 11)
+}
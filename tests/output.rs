@@ -0,0 +1,130 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+extern crate arrayvec;
+extern crate heapless;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate ssmarshal;
+
+mod common;
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::mem::MaybeUninit;
+
+use ssmarshal::output::{HashOutput, Inspect, UninitOutput};
+use ssmarshal::{DefaultOptions, Error, Output, Serializer};
+
+use common::{encoded, message};
+
+fn write<W: Output>(out: W) -> Result<W, Error> {
+    let mut serializer = Serializer::from_output(out, DefaultOptions::new());
+    serializer.serialize(&message())?;
+    Ok(serializer.into_output())
+}
+
+/// A CRC-32 (IEEE) computed a bit at a time.
+fn crc32(crc: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[test]
+fn heapless_vec() {
+    let out = write(heapless::Vec::<u8, 32>::new()).unwrap();
+    assert_eq!(out[..], encoded(&message())[..]);
+}
+
+#[test]
+fn array_vec() {
+    let out = write(arrayvec::ArrayVec::<u8, 32>::new()).unwrap();
+    assert_eq!(out[..], encoded(&message())[..]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn vec() {
+    let out = write(Vec::new()).unwrap();
+    assert_eq!(out, encoded(&message()));
+
+    let mut serializer = Serializer::from_output(Vec::new(), DefaultOptions::new());
+    serializer.serialize(&[7u8; 100][..]).unwrap();
+    assert!(serializer.into_output().capacity() >= 108);
+}
+
+#[test]
+fn uninit() {
+    let mut buf = [MaybeUninit::<u8>::uninit(); 32];
+    let out = write(UninitOutput::new(&mut buf)).unwrap();
+    assert_eq!(out.remaining(), 4);
+    assert_eq!(out.into_init()[..], encoded(&message())[..]);
+}
+
+#[cfg(not(feature = "debug-panics"))]
+#[test]
+fn full() {
+    use ssmarshal::ErrorKind;
+
+    let err = write(heapless::Vec::<u8, 8>::new()).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::EndOfStream);
+    assert_eq!(err.offset(), Some(4));
+    let err = write(arrayvec::ArrayVec::<u8, 8>::new()).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::EndOfStream);
+    let mut buf = [MaybeUninit::<u8>::uninit(); 4];
+    let err = write(UninitOutput::new(&mut buf)).err().unwrap();
+    assert_eq!(err.kind(), &ErrorKind::EndOfStream);
+    assert_eq!(err.offset(), Some(4));
+}
+
+#[test]
+fn hasher() {
+    let out = write(HashOutput::new(DefaultHasher::new())).unwrap();
+    assert_eq!(out.position(), 28);
+    let mut expected = DefaultHasher::new();
+    expected.write(&encoded(&message()));
+    assert_eq!(out.finish(), expected.finish());
+}
+
+#[test]
+fn inspect_crc() {
+    let mut buf = [0u8; 32];
+    let mut crc = 0;
+    let out = Inspect::new(ssmarshal::SliceOutput::new(&mut buf), |bytes: &[u8]| {
+        crc = crc32(crc, bytes);
+    });
+    let len = write(out).unwrap().position();
+    assert_eq!(len, 28);
+    assert_eq!(crc, crc32(0, &encoded(&message())));
+    assert_eq!(crc32(0, b"123456789"), 0xCBF4_3926);
+}
+
+/// Outputs can be implemented outside the crate.
+struct Discard(usize);
+
+impl Output for Discard {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.0 += bytes.len();
+        Ok(())
+    }
+
+    fn position(&self) -> usize {
+        self.0
+    }
+}
+
+#[test]
+fn custom() {
+    assert_eq!(write(Discard(0)).unwrap().position(), 28);
+}
@@ -4,17 +4,19 @@
 // carrying such notice may not be copied, modified, or distributed except according to those
 // terms.
 
-#![feature(proc_macro, plugin)]
-#![plugin(quickcheck_macros)]
-#![allow(non_snake_case)]
+#![allow(non_snake_case, clippy::disallowed_names)]
 
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate quickcheck;
+extern crate quickcheck_macros;
+extern crate rand;
 
 extern crate ssmarshal;
 
+use quickcheck_macros::quickcheck;
+use rand::Rng;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
@@ -32,6 +34,9 @@ struct U32(u32);
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct U64(u64);
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct U128(u128);
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct USize(usize);
 
@@ -47,6 +52,9 @@ struct I32(i32);
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct I64(i64);
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct I128(i128);
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct ISize(isize);
 
@@ -209,10 +217,12 @@ rt! {
     (rt_i16, i16),
     (rt_i32, i32),
     (rt_i64, i64),
+    (rt_i128, i128),
     (rt_u8,  u8),
     (rt_u16, u16),
     (rt_u32, u32),
     (rt_u64, u64),
+    (rt_u128, u128),
     (rt_usize, usize),
     (rt_isize, isize),
     (rt_f32, f32),
@@ -221,6 +231,7 @@ rt! {
     (rt_tp2, (u8, i16, u8)),
     (rt_tp3, (u8, i16, u8)),
     (rt_tp4, (usize, i16, isize)),
+    (rt_tp5, (u8, u128, i128)),
     (rt_char, char)
 }
 
@@ -229,10 +240,14 @@ rt_wrap! {
     (rt_I16, i16, I16),
     (rt_I32, i32, I32),
     (rt_I64, i64, I64),
+    (rt_I128, i128, I128),
     (rt_U8,  u8 , U8),
     (rt_U16, u16, U16),
     (rt_U32, u32, U32),
     (rt_U64, u64, U64),
+    (rt_U128, u128, U128),
+    (rt_USize, usize, USize),
+    (rt_ISize, isize, ISize),
     (rt_F32, f32, F32),
     (rt_F64, f64, F64)
 }
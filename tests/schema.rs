@@ -0,0 +1,326 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+#![cfg(feature = "schema")]
+
+extern crate heapless;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate ssmarshal;
+
+use std::num::NonZeroU32;
+
+use ssmarshal::config::{BigEndian, SelfDescribing, U32};
+use ssmarshal::schema::{self, Container, Encoding, Field, Format, Schema, Variant, VariantFormat};
+use ssmarshal::{Config, MaxSize};
+
+fn named(name: &str) -> Format {
+    Format::Named(name.into())
+}
+
+fn field(name: &str, format: Format) -> Field {
+    Field {
+        name: name.into(),
+        format,
+    }
+}
+
+fn variant(name: &str, contents: VariantFormat) -> Variant {
+    Variant {
+        name: name.into(),
+        contents,
+    }
+}
+
+#[derive(Serialize, Deserialize, MaxSize)]
+struct Header {
+    id: u32,
+    reply: Option<u16>,
+    origin: (i8, char),
+    mask: [u8; 3],
+}
+
+#[derive(Serialize, Deserialize, MaxSize)]
+enum Command {
+    Stop,
+    Move(i32, i32),
+    Rename(Header),
+    Configure { speed: f32, flags: Unit },
+}
+
+#[derive(Serialize, Deserialize, MaxSize)]
+struct Unit;
+
+#[derive(Serialize, Deserialize, MaxSize)]
+struct Message {
+    header: Header,
+    commands: [Command; 2],
+}
+
+#[test]
+fn structs_and_enums() {
+    let schema = schema::trace::<Message>().unwrap();
+    assert_eq!(schema.root, named("Message"));
+    assert_eq!(schema.encoding, Encoding::default());
+    assert_eq!(
+        schema.types["Message"],
+        Container::Struct(vec![
+            field("header", named("Header")),
+            field(
+                "commands",
+                Format::Array {
+                    element: Box::new(named("Command")),
+                    len: 2,
+                }
+            ),
+        ])
+    );
+    assert_eq!(
+        schema.types["Header"],
+        Container::Struct(vec![
+            field("id", Format::U32),
+            field("reply", Format::Option(Box::new(Format::U16))),
+            field("origin", Format::Tuple(vec![Format::I8, Format::Char])),
+            field(
+                "mask",
+                Format::Array {
+                    element: Box::new(Format::U8),
+                    len: 3,
+                }
+            ),
+        ])
+    );
+    assert_eq!(
+        schema.types["Command"],
+        Container::Enum(vec![
+            variant("Stop", VariantFormat::Unit),
+            variant("Move", VariantFormat::Tuple(vec![Format::I32, Format::I32])),
+            variant("Rename", VariantFormat::Newtype(named("Header"))),
+            variant(
+                "Configure",
+                VariantFormat::Struct(vec![
+                    field("speed", Format::F32),
+                    field("flags", named("Unit")),
+                ])
+            ),
+        ])
+    );
+    assert_eq!(schema.types["Unit"], Container::UnitStruct);
+    assert_eq!(schema.types.len(), 4);
+}
+
+#[test]
+fn max_size() {
+    assert_eq!(
+        schema::trace::<Message>().unwrap().max_size(),
+        Some(Message::MAX_SIZE)
+    );
+    assert_eq!(
+        schema::trace::<Option<Command>>().unwrap().max_size(),
+        Some(Option::<Command>::MAX_SIZE)
+    );
+    assert_eq!(schema::trace::<(u8, String)>().unwrap().max_size(), None);
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+enum List {
+    Cons(u8, Box<List>),
+    Nil,
+}
+
+#[derive(Deserialize)]
+struct Tree {
+    children: Vec<Tree>,
+    kind: Kind,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+enum Kind {
+    Leaf(Option<Box<Tree>>),
+    Branch,
+}
+
+#[test]
+fn recursive() {
+    let schema = schema::trace::<List>().unwrap();
+    assert_eq!(
+        schema.types["List"],
+        Container::Enum(vec![
+            variant(
+                "Cons",
+                VariantFormat::Tuple(vec![Format::U8, named("List")])
+            ),
+            variant("Nil", VariantFormat::Unit),
+        ])
+    );
+    assert_eq!(schema.max_size(), None);
+
+    let schema = schema::trace::<Tree>().unwrap();
+    assert_eq!(
+        schema.types["Tree"],
+        Container::Struct(vec![
+            field("children", Format::Seq(Box::new(named("Tree")))),
+            field("kind", named("Kind")),
+        ])
+    );
+    assert_eq!(
+        schema.types["Kind"],
+        Container::Enum(vec![
+            variant(
+                "Leaf",
+                VariantFormat::Newtype(Format::Option(Box::new(named("Tree"))))
+            ),
+            variant("Branch", VariantFormat::Unit),
+        ])
+    );
+}
+
+#[derive(Deserialize)]
+struct Endless {
+    _next: Box<Endless>,
+}
+
+#[derive(Deserialize)]
+struct Flat {
+    #[serde(flatten)]
+    _inner: Header,
+}
+
+#[test]
+fn untraceable() {
+    assert!(schema::trace::<Endless>().is_err());
+    assert!(schema::trace::<Flat>().is_err());
+}
+
+#[derive(Serialize, Deserialize, MaxSize)]
+struct Special {
+    #[serde(with = "ssmarshal::word")]
+    len: usize,
+    #[serde(with = "ssmarshal::niche")]
+    badge: Option<NonZeroU32>,
+    nonzero: NonZeroU32,
+    #[serde(with = "ssmarshal::bounded::heapless_vec")]
+    items: heapless::Vec<u16, 300>,
+    #[serde(with = "ssmarshal::bounded::heapless_string")]
+    name: heapless::String<8>,
+}
+
+#[test]
+fn special_encodings() {
+    let schema = schema::trace::<Special>().unwrap();
+    assert_eq!(
+        schema.types["Special"],
+        Container::Struct(vec![
+            field("len", Format::Usize),
+            field("badge", Format::U32),
+            field("nonzero", Format::U32),
+            field(
+                "items",
+                Format::Bounded {
+                    element: Box::new(Format::U16),
+                    capacity: 300,
+                }
+            ),
+            field(
+                "name",
+                Format::Bounded {
+                    element: Box::new(Format::U8),
+                    capacity: 8,
+                }
+            ),
+        ])
+    );
+    // the derived `MaxSize` counts the niche as an `Option` and full 8-byte collection prefixes
+    assert_eq!(schema.max_size(), Some(8 + 4 + 4 + 2 + 300 * 2 + 1 + 8));
+    assert_eq!(schema.max_size(), Some(Special::MAX_SIZE - 1 - 6 - 7));
+}
+
+#[test]
+fn options() {
+    let opts = Config::new().with_endian::<BigEndian>().with_usize::<U32>();
+    let schema = schema::trace_with::<Special, _>(opts).unwrap();
+    assert!(schema.encoding.big_endian);
+    assert_eq!(schema.encoding.usize_width, 4);
+    assert_eq!(schema.max_size(), Some(Special::MAX_SIZE - 1 - 6 - 7 - 4));
+}
+
+#[derive(Serialize, Deserialize)]
+struct Point {
+    x: i16,
+    y: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Step {
+    Stay,
+    Go(Point),
+}
+
+#[test]
+fn self_describing_max_size() {
+    let opts = Config::new().with_format::<SelfDescribing>();
+    let schema = schema::trace_with::<[Step; 2], _>(opts).unwrap();
+    let mut buf = [0u8; 256];
+    let val = [Step::Stay, Step::Go(Point { x: 1, y: 2 })];
+    let len = ssmarshal::serialize_with(&mut buf, &val, opts).unwrap();
+    let stay = ssmarshal::serialize_with(&mut buf, &Step::Stay, opts).unwrap();
+    let go = ssmarshal::serialize_with(&mut buf, &Step::Go(Point { x: 1, y: 2 }), opts).unwrap();
+    assert_eq!(len, 9 + stay + go);
+    assert_eq!(schema.max_size(), Some(9 + 2 * go));
+}
+
+#[test]
+fn schema_roundtrip() {
+    let schema = schema::trace::<Message>().unwrap();
+    let buf = ssmarshal::to_vec(&schema).unwrap();
+    let mut de = ssmarshal::Deserializer::new(&buf);
+    assert_eq!(de.deserialize::<Schema>().unwrap(), schema);
+    de.end().unwrap();
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn json() {
+    let schema = schema::trace::<Point>().unwrap();
+    assert_eq!(
+        schema.to_json(),
+        r#"{
+  "encoding": {
+    "big_endian": false,
+    "usize_width": 8,
+    "len_width": 8,
+    "tag_width": 1,
+    "self_describing": false,
+    "canonical": false
+  },
+  "root": {
+    "Named": "Point"
+  },
+  "types": {
+    "Point": {
+      "Struct": [
+        {
+          "name": "x",
+          "format": "I16"
+        },
+        {
+          "name": "y",
+          "format": "U8"
+        }
+      ]
+    }
+  }
+}"#
+    );
+    assert_eq!(Schema::from_json(&schema.to_json()).unwrap(), schema);
+
+    let schema = schema::trace::<Message>().unwrap();
+    assert_eq!(Schema::from_json(&schema.to_json()).unwrap(), schema);
+}
@@ -0,0 +1,296 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+extern crate quickcheck;
+extern crate quickcheck_macros;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate ssmarshal;
+
+use serde::de::{Deserialize, IgnoredAny};
+use serde::Serialize;
+
+use ssmarshal::config::{Bare, Config, Lenient, LittleEndian, SelfDescribing, U64, U8};
+use ssmarshal::{deserialize_with, serialize, serialize_with, Deserializer, ErrorKind};
+
+type Described = Config<LittleEndian, U64, U64, U8, Lenient, SelfDescribing>;
+
+fn described() -> Described {
+    Config::new().with_format::<SelfDescribing>()
+}
+
+fn encode<T: Serialize + ?Sized>(val: &T) -> Vec<u8> {
+    let mut buf = [0u8; 1024];
+    let len = serialize_with(&mut buf, val, described()).unwrap();
+    buf[..len].to_vec()
+}
+
+fn decode<'de, T: Deserialize<'de>>(buf: &'de [u8]) -> T {
+    let mut de = Deserializer::with_options(buf, described());
+    let val = T::deserialize(&mut de).unwrap();
+    de.end().unwrap();
+    val
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Shape {
+    Empty,
+    Circle(u16),
+    Rect(u8, u8),
+    Polygon { sides: u8, regular: bool },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Drawing<'a> {
+    name: &'a str,
+    shapes: [Shape; 4],
+    scale: Option<f32>,
+    anchor: (i16, char),
+    #[serde(with = "ssmarshal::word")]
+    layer: usize,
+}
+
+fn drawing() -> Drawing<'static> {
+    Drawing {
+        name: "plan",
+        shapes: [
+            Shape::Empty,
+            Shape::Circle(300),
+            Shape::Rect(2, 3),
+            Shape::Polygon {
+                sides: 6,
+                regular: true,
+            },
+        ],
+        scale: Some(0.5),
+        anchor: (-4, 'λ'),
+        layer: 7,
+    }
+}
+
+#[test]
+fn roundtrip() {
+    let buf = encode(&drawing());
+    assert_eq!(decode::<Drawing>(&buf), drawing());
+}
+
+#[test]
+fn markers() {
+    assert_eq!(encode(&true), [0x01, 1]);
+    assert_eq!(encode(&-2i16), [0x08, 0xFE, 0xFF]);
+    assert_eq!(encode(&()), [0x00]);
+    assert_eq!(encode(&None::<u8>), [0x11]);
+    assert_eq!(encode(&Some(3u8)), [0x12, 0x02, 3]);
+    assert_eq!(encode(&(1u8, 'a')), {
+        let mut v = vec![0x13];
+        v.extend_from_slice(&2u64.to_le_bytes());
+        v.extend_from_slice(&[0x02, 1, 0x0E, b'a']);
+        v
+    });
+
+    let mut expected = vec![0x15, 1, 0x0F];
+    expected.extend_from_slice(&6u64.to_le_bytes());
+    expected.extend_from_slice(b"Circle");
+    expected.extend_from_slice(&[0x03, 0x2C, 0x01]);
+    assert_eq!(encode(&Shape::Circle(300)), expected);
+}
+
+#[test]
+fn struct_has_field_names() {
+    #[derive(Serialize)]
+    struct Point {
+        x: u8,
+    }
+
+    let mut expected = vec![0x14];
+    expected.extend_from_slice(&1u64.to_le_bytes());
+    expected.push(0x0F);
+    expected.extend_from_slice(&1u64.to_le_bytes());
+    expected.extend_from_slice(&[b'x', 0x02, 5]);
+    assert_eq!(encode(&Point { x: 5 }), expected);
+}
+
+#[test]
+fn words_are_u64() {
+    let buf = encode(&drawing());
+    let tail = buf.len() - 9;
+    assert_eq!(buf[tail], 0x05);
+    assert_eq!(buf[tail + 1..], 7u64.to_le_bytes());
+}
+
+#[test]
+fn ignored_any() {
+    let mut buf = encode(&drawing());
+    buf.extend_from_slice(&encode(&9u8));
+    let mut de = Deserializer::with_options(&buf, described());
+    IgnoredAny::deserialize(&mut de).unwrap();
+    assert_eq!(u8::deserialize(&mut de).unwrap(), 9);
+    de.end().unwrap();
+}
+
+#[test]
+fn borrows_strings() {
+    let buf = encode(&("borrowed", 1u8));
+    let (s, n): (&str, u8) = decode(&buf);
+    assert_eq!((s, n), ("borrowed", 1));
+}
+
+#[test]
+fn bare_unchanged() {
+    let mut bare = [0u8; 256];
+    let mut default = [0u8; 256];
+    let len = serialize_with(&mut bare, &drawing(), Config::new().with_format::<Bare>()).unwrap();
+    assert_eq!(serialize(&mut default, &drawing()).unwrap(), len);
+    assert_eq!(bare[..len], default[..len]);
+}
+
+#[cfg(not(feature = "debug-panics"))]
+#[test]
+fn bare_has_no_any() {
+    let mut buf = [0u8; 8];
+    let len = serialize(&mut buf, &1u8).unwrap();
+    let err = IgnoredAny::deserialize(&mut Deserializer::new(&buf[..len])).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::NotSupported);
+}
+
+#[test]
+fn invalid_marker() {
+    let err = deserialize_with::<u8, _>(&[0x16, 1], described()).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::InvalidMarker(0x16));
+    assert_eq!(err.offset(), Some(0));
+
+    let mut buf = encode(&(0u8, Shape::Empty));
+    let last = buf.len() - 1;
+    buf[last] = 0x01;
+    let err = deserialize_with::<(u8, Shape), _>(&buf, described()).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::InvalidMarker(0x01));
+    assert_eq!(err.offset(), Some(last));
+
+    let buf = encode(&7u8);
+    let err = deserialize_with::<Shape, _>(&buf, described()).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::InvalidMarker(0x02));
+}
+
+#[cfg(feature = "alloc")]
+mod needs_any {
+    use super::*;
+
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Header {
+        id: u32,
+        flags: u8,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Packet {
+        #[serde(flatten)]
+        header: Header,
+        payload: Vec<u8>,
+    }
+
+    #[test]
+    fn flatten() {
+        let packet = Packet {
+            header: Header { id: 9, flags: 3 },
+            payload: vec![1, 2, 3],
+        };
+        let buf = encode(&packet);
+        assert_eq!(decode::<Packet>(&buf), packet);
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(tag = "type")]
+    enum Event {
+        Key { code: u16, down: bool },
+        Resize { width: u32, height: u32 },
+        Quit,
+    }
+
+    #[test]
+    fn internally_tagged() {
+        for event in [
+            Event::Key {
+                code: 30,
+                down: true,
+            },
+            Event::Resize {
+                width: 80,
+                height: 24,
+            },
+            Event::Quit,
+        ] {
+            let buf = encode(&event);
+            assert_eq!(decode::<Event>(&buf), event);
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Loose {
+        Number(i64),
+        Text(String),
+        Pair(u8, u8),
+    }
+
+    #[test]
+    fn untagged() {
+        for val in [
+            Loose::Number(-5),
+            Loose::Text("hi".into()),
+            Loose::Pair(1, 2),
+        ] {
+            let buf = encode(&val);
+            assert_eq!(decode::<Loose>(&buf), val);
+        }
+    }
+
+    #[test]
+    fn maps() {
+        let mut map = BTreeMap::new();
+        map.insert("a".to_string(), vec![Some(1u8), None]);
+        map.insert("b".to_string(), vec![]);
+        let buf = encode(&map);
+        assert_eq!(decode::<BTreeMap<String, Vec<Option<u8>>>>(&buf), map);
+    }
+
+    // an enum decoded without its type comes out as a map from the variant name
+    #[test]
+    fn enum_as_map() {
+        let buf = encode(&Shape::Rect(2, 3));
+        let map: BTreeMap<String, (u8, u8)> = decode(&buf);
+        assert_eq!(
+            map.into_iter().collect::<Vec<_>>(),
+            [("Rect".into(), (2, 3))]
+        );
+    }
+
+    // owned copies of field names are decoded from inputs which can't lend them out
+    #[test]
+    fn non_borrowing_input() {
+        use ssmarshal::input::IterInput;
+
+        let buf = encode(&Packet {
+            header: Header { id: 1, flags: 0 },
+            payload: vec![4],
+        });
+        let mut de = Deserializer::from_input(IterInput::new(buf.iter().cloned()), described());
+        let packet = Packet::deserialize(&mut de).unwrap();
+        assert_eq!(packet.payload, [4]);
+    }
+}
+
+#[quickcheck_macros::quickcheck]
+fn arbitrary(val: (u16, Option<i64>, char, (bool, f64), String)) -> bool {
+    let buf = encode(&(val.0, val.1, val.2, val.3, &val.4[..]));
+    let back: (u16, Option<i64>, char, (bool, f64), &str) = decode(&buf);
+    (back.0, back.1, back.2, back.4) == (val.0, val.1, val.2, &val.4[..])
+        && back.3 .0 == val.3 .0
+        && back.3 .1.to_bits() == val.3 .1.to_bits()
+}
@@ -0,0 +1,92 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+extern crate quickcheck;
+extern crate quickcheck_macros;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate ssmarshal;
+
+use quickcheck_macros::quickcheck;
+
+use ssmarshal::config::{U16, U32};
+use ssmarshal::{
+    serialize, serialize_with, serialized_size, serialized_size_with, Config, ErrorKind,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Frame {
+    Ack(u32),
+    Data { seq: u16, payload: [u8; 4] },
+    Close(Option<char>),
+}
+
+/// A sequence whose length isn't known up front.
+struct Filtered;
+
+impl serde::Serialize for Filtered {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq((0..3u8).filter(|_| true))
+    }
+}
+
+#[test]
+fn matches_serialize() {
+    let frames = [
+        Frame::Ack(1),
+        Frame::Data {
+            seq: 2,
+            payload: [1, 2, 3, 4],
+        },
+        Frame::Close(None),
+        Frame::Close(Some('€')),
+    ];
+    for frame in &frames {
+        let mut buf = [0u8; 16];
+        let len = serialize(&mut buf, frame).unwrap();
+        assert_eq!(serialized_size(frame).unwrap(), len, "{:?}", frame);
+    }
+    assert_eq!(serialized_size(&()).unwrap(), 0);
+    assert_eq!(serialized_size("abc").unwrap(), 11);
+}
+
+#[test]
+fn with_options() {
+    let opts = Config::new().with_len::<U16>().with_tag::<U32>();
+    let val = (Frame::Ack(7), &b"xyz"[..]);
+    let mut buf = [0u8; 32];
+    let len = serialize_with(&mut buf, &val, opts).unwrap();
+    assert_eq!(len, 13);
+    assert_eq!(serialized_size_with(&val, opts).unwrap(), len);
+}
+
+#[test]
+fn unknown_length() {
+    assert_eq!(serialized_size(&Filtered).unwrap(), 11);
+}
+
+#[test]
+fn overflow() {
+    let opts = Config::new().with_len::<U16>();
+    let err = serialized_size_with(&[0u8; 70_000][..], opts).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::Overflow);
+}
+
+#[cfg(feature = "alloc")]
+#[quickcheck]
+fn matches_to_vec(val: Vec<(String, Option<u64>)>) -> bool {
+    serialized_size(&val).unwrap() == ssmarshal::to_vec(&val).unwrap().len()
+}
+
+#[quickcheck]
+fn matches_serialize_qc(a: u32, b: Option<i16>, c: (bool, char)) -> bool {
+    let val = (a, b, c);
+    let mut buf = [0u8; 32];
+    let len = serialize(&mut buf, &val).unwrap();
+    serialized_size(&val).unwrap() == len
+}
@@ -0,0 +1,99 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+extern crate quickcheck;
+extern crate quickcheck_macros;
+extern crate serde;
+
+extern crate ssmarshal;
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::num::{NonZeroI64, NonZeroU16, NonZeroU8, Wrapping};
+use std::ops::{Bound, Range, RangeInclusive};
+use std::time::Duration;
+
+use quickcheck_macros::quickcheck;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use ssmarshal::{deserialize, serialize};
+
+/// Roundtrip `val`, checking it fits in `size_of::<T>()` bytes.
+fn rt<T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug>(val: &T) -> usize {
+    let mut buf = vec![0; std::mem::size_of::<T>()];
+    let len = serialize(&mut buf, val).unwrap();
+    let (new_val, new_len): (T, usize) = deserialize(&buf).unwrap();
+    assert_eq!(val, &new_val);
+    assert_eq!(len, new_len);
+    len
+}
+
+#[test]
+fn ip_addr() {
+    assert_eq!(rt(&IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))), 5);
+    assert_eq!(rt(&"::1".parse::<IpAddr>().unwrap()), 17);
+    assert_eq!(rt(&Ipv4Addr::LOCALHOST), 4);
+}
+
+#[test]
+fn socket_addr() {
+    assert_eq!(rt(&"127.0.0.1:80".parse::<SocketAddr>().unwrap()), 7);
+    assert_eq!(rt(&"[::1]:443".parse::<SocketAddr>().unwrap()), 19);
+}
+
+#[test]
+fn duration() {
+    assert_eq!(rt(&Duration::new(5, 999_999_999)), 12);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn system_time() {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    assert_eq!(rt(&(UNIX_EPOCH + Duration::new(1_500_000_000, 7))), 12);
+    rt(&SystemTime::now());
+}
+
+#[test]
+fn nonzero() {
+    assert_eq!(rt(&NonZeroU8::new(3).unwrap()), 1);
+    assert_eq!(rt(&NonZeroI64::new(-3).unwrap()), 8);
+    let mut buf = [0u8; 2];
+    serialize(&mut buf, &0u16).unwrap();
+    assert!(deserialize::<NonZeroU16>(&buf).is_err());
+}
+
+#[test]
+fn wrapping() {
+    assert_eq!(rt(&Wrapping(u32::MAX)), 4);
+}
+
+#[test]
+fn ranges() {
+    assert_eq!(rt(&(3u16..9)), 4);
+    assert_eq!(rt(&(-1i8..=1)), 2);
+    assert_eq!(rt(&Bound::Included(7u64)), 9);
+    assert_eq!(rt(&Bound::<u64>::Unbounded), 1);
+}
+
+#[quickcheck]
+fn rt_socket_addr(ip: IpAddr, port: u16) -> bool {
+    // only the address and port are encoded, so leave the V6 flow info and scope at 0
+    rt(&SocketAddr::new(ip, port));
+    true
+}
+
+#[quickcheck]
+fn rt_duration(val: Duration) -> bool {
+    rt(&val);
+    true
+}
+
+#[quickcheck]
+fn rt_core(val: (Wrapping<i32>, Range<u8>, RangeInclusive<i16>, Bound<u32>)) -> bool {
+    rt(&val);
+    true
+}
@@ -0,0 +1,204 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+extern crate quickcheck;
+extern crate quickcheck_macros;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate ssmarshal;
+
+use quickcheck_macros::quickcheck;
+use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
+
+use ssmarshal::config::{Canonical, Strict};
+use ssmarshal::{deserialize, deserialize_with, serialize_with, Config, ErrorKind};
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+enum Reading {
+    Missing,
+    Temp(f32),
+    Pos { lat: f64, valid: bool },
+}
+
+type Signed = (u16, Option<char>, Reading, (i8, i8));
+
+/// A sequence of `u8`s, of which only the first is kept.
+#[derive(Debug, PartialEq)]
+struct First(u8);
+
+impl<'de> Deserialize<'de> for First {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<First, D::Error> {
+        struct FirstVisitor;
+
+        impl<'de> Visitor<'de> for FirstVisitor {
+            type Value = First;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a non-empty sequence")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<First, A::Error> {
+                Ok(First(seq.next_element()?.unwrap_or(0)))
+            }
+        }
+
+        deserializer.deserialize_tuple(3, FirstVisitor)
+    }
+}
+
+#[test]
+fn trailing_bytes() {
+    let buf = [1, 0, 0];
+    assert_eq!(deserialize::<u16>(&buf).unwrap(), (1, 2));
+    let err = deserialize_with::<u16, _>(&buf, Config::new().with_mode::<Strict>()).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::TrailingBytes);
+    assert_eq!(err.offset(), Some(2));
+    let opts = Config::new().with_mode::<Strict>();
+    assert_eq!(deserialize_with::<u16, _>(&buf[..2], opts).unwrap(), (1, 2));
+}
+
+#[test]
+fn more_elements() {
+    let buf = [4, 5, 6];
+    assert_eq!(deserialize::<First>(&buf).unwrap(), (First(4), 1));
+    let err = deserialize_with::<First, _>(&buf, Config::new().with_mode::<Strict>()).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::MoreElements);
+    assert_eq!(err.offset(), Some(1));
+}
+
+#[test]
+fn canonical_nan() {
+    let mut buf = [0u8; 4];
+    let odd_nan = f32::from_bits(0xFFC0_0001);
+    let opts = Config::new().with_mode::<Canonical>();
+
+    serialize_with(&mut buf, &odd_nan, Config::new()).unwrap();
+    assert_eq!(buf, 0xFFC0_0001u32.to_le_bytes());
+    let strict = Config::new().with_mode::<Strict>();
+    assert!(deserialize_with::<f32, _>(&buf, strict).unwrap().0.is_nan());
+    let err = deserialize_with::<f32, _>(&buf, opts).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::NonCanonicalNan);
+
+    serialize_with(&mut buf, &odd_nan, opts).unwrap();
+    assert_eq!(buf, 0x7FC0_0000u32.to_le_bytes());
+    assert!(deserialize_with::<f32, _>(&buf, opts).unwrap().0.is_nan());
+
+    let mut buf = [0u8; 8];
+    serialize_with(&mut buf, &-f64::NAN, opts).unwrap();
+    assert_eq!(buf, 0x7FF8_0000_0000_0000u64.to_le_bytes());
+}
+
+/// Maps have no canonical encoding, whether their entries are sorted, reordered or duplicated.
+#[cfg(feature = "alloc")]
+#[test]
+fn canonical_maps() {
+    use std::collections::BTreeMap;
+
+    let opts = Config::new().with_mode::<Canonical>();
+    let strict = Config::new().with_mode::<Strict>();
+    let map: BTreeMap<u8, u8> = vec![(1, 10), (2, 20)].into_iter().collect();
+    let mut buf = [0u8; 16];
+    let err = serialize_with(&mut buf, &map, opts).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::NonCanonicalMap);
+
+    let sorted = [2, 0, 0, 0, 0, 0, 0, 0, 1, 10, 2, 20];
+    let reordered = [2, 0, 0, 0, 0, 0, 0, 0, 2, 20, 1, 10];
+    let duplicated = [2, 0, 0, 0, 0, 0, 0, 0, 1, 10, 1, 10];
+    assert_eq!(serialize_with(&mut buf, &map, strict).unwrap(), 12);
+    assert_eq!(buf[..12], sorted);
+    for bytes in [&sorted, &reordered, &duplicated].iter() {
+        let err = deserialize_with::<BTreeMap<u8, u8>, _>(&bytes[..], opts).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::NonCanonicalMap);
+        assert_eq!(err.offset(), Some(0));
+    }
+    // outside canonical mode, all three decode to a valid map
+    let (val, _) = deserialize_with::<BTreeMap<u8, u8>, _>(&reordered, strict).unwrap();
+    assert_eq!(val, map);
+    let (val, _) = deserialize_with::<BTreeMap<u8, u8>, _>(&duplicated, strict).unwrap();
+    assert_eq!(val.len(), 1);
+}
+
+/// The self-describing format writes maps and structs with the same marker, and types which
+/// decode through `deserialize_any` see it without going through `deserialize_map`.
+#[cfg(feature = "alloc")]
+#[test]
+fn canonical_self_describing_maps() {
+    use ssmarshal::config::SelfDescribing;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Deserialize)]
+    #[serde(untagged)]
+    enum Any {
+        Map(BTreeMap<u8, u8>),
+    }
+
+    let opts = Config::new()
+        .with_mode::<Canonical>()
+        .with_format::<SelfDescribing>();
+    let strict = Config::new()
+        .with_mode::<Strict>()
+        .with_format::<SelfDescribing>();
+    let map: BTreeMap<u8, u8> = vec![(2, 20), (1, 10)].into_iter().collect();
+    let mut buf = [0u8; 64];
+    let len = serialize_with(&mut buf, &map, strict).unwrap();
+    let (Any::Map(read), _) = deserialize_with::<Any, _>(&buf[..len], strict).unwrap();
+    assert_eq!(read, map);
+    let err = deserialize_with::<Any, _>(&buf[..len], opts).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::NonCanonicalMap);
+    assert_eq!(err.offset(), Some(0));
+
+    let pos = Reading::Pos {
+        lat: 1.0,
+        valid: true,
+    };
+    let len = serialize_with(&mut buf, &pos, strict).unwrap();
+    let err = deserialize_with::<Reading, _>(&buf[..len], opts).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::NonCanonicalMap);
+    let err = serialize_with(&mut buf, &pos, opts).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::NonCanonicalMap);
+}
+
+/// Any buffer accepted in canonical mode is the only encoding of its value.
+#[cfg(not(feature = "debug-panics"))]
+#[quickcheck]
+fn unique_encoding(buf: Vec<u8>) -> bool {
+    // trim the buffer to what a lenient decode reads, so most inputs get checked at all
+    let buf = match deserialize::<Signed>(&buf) {
+        Ok((_, len)) => &buf[..len],
+        Err(_) => &buf[..],
+    };
+    let opts = Config::new().with_mode::<Canonical>();
+    match deserialize_with::<Signed, _>(buf, opts) {
+        Ok((val, _)) => {
+            let mut out = [0u8; 64];
+            let len = serialize_with(&mut out, &val, opts).unwrap();
+            out[..len] == buf[..]
+        }
+        Err(_) => true,
+    }
+}
+
+#[quickcheck]
+fn rt_canonical(a: u16, b: Option<char>, variant: u8, temp: f32, lat: f64, d: (i8, i8)) -> bool {
+    let reading = match variant % 3 {
+        0 => Reading::Missing,
+        1 => Reading::Temp(temp),
+        _ => Reading::Pos {
+            lat,
+            valid: variant > 127,
+        },
+    };
+    let val: Signed = (a, b, reading, d);
+    let opts = Config::new().with_mode::<Canonical>();
+    let mut buf = [0u8; 64];
+    let len = serialize_with(&mut buf, &val, opts).unwrap();
+    let (new_val, new_len): (Signed, usize) = deserialize_with(&buf[..len], opts).unwrap();
+    let mut again = [0u8; 64];
+    let again_len = serialize_with(&mut again, &new_val, opts).unwrap();
+    new_len == len && buf[..len] == again[..again_len]
+}
@@ -0,0 +1,170 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+extern crate serde;
+
+extern crate ssmarshal;
+
+use std::mem::MaybeUninit;
+
+use serde::{Serialize, Serializer};
+
+use ssmarshal::config::{BigEndian, U16, U8};
+use ssmarshal::output::UninitOutput;
+use ssmarshal::{deserialize, serialize_with, Config, DefaultOptions, ErrorKind};
+
+/// The even numbers below `end`, streamed without a length.
+struct Evens(u16);
+
+impl Serialize for Evens {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq((0..self.0).filter(|n| n % 2 == 0))
+    }
+}
+
+/// Pairs of each even number below `end` and its half, streamed without a length.
+struct Halves(u8);
+
+impl Serialize for Halves {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map((0..self.0).filter(|n| n % 2 == 0).map(|n| (n, n / 2)))
+    }
+}
+
+fn known(end: u16) -> Vec<u16> {
+    (0..end).filter(|n| n % 2 == 0).collect()
+}
+
+// `serialize` would check the size invariant, which these types don't keep
+fn encode<T: Serialize + ?Sized>(buf: &mut [u8], val: &T) -> usize {
+    serialize_with(buf, val, DefaultOptions::new()).unwrap()
+}
+
+#[test]
+fn same_as_known_length() {
+    let mut unknown = [0u8; 64];
+    let mut expected = [0u8; 64];
+    let len = encode(&mut unknown, &Evens(9));
+    assert_eq!(encode(&mut expected, &known(9)[..]), len);
+    assert_eq!(len, 18);
+    assert_eq!(unknown[..len], expected[..len]);
+    assert_eq!(unknown[..8], [5, 0, 0, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn decodes() {
+    let mut buf = [0u8; 64];
+    let len = encode(&mut buf, &(Evens(5), 7u8));
+    assert_eq!(
+        deserialize::<(u64, u16, u16, u16, u8)>(&buf[..len]).unwrap(),
+        ((3, 0, 2, 4, 7), len)
+    );
+}
+
+#[test]
+fn empty() {
+    let mut buf = [0xFFu8; 8];
+    assert_eq!(encode(&mut buf, &Evens(0)), 8);
+    assert_eq!(buf, [0; 8]);
+}
+
+#[test]
+fn nested() {
+    let mut buf = [0u8; 64];
+    let len = encode(&mut buf, &[Evens(3), Evens(0)]);
+    let mut expected = [0u8; 64];
+    assert_eq!(encode(&mut expected, &[&known(3)[..], &known(0)[..]]), len);
+    assert_eq!(buf[..len], expected[..len]);
+}
+
+#[test]
+fn options() {
+    let opts = Config::new().with_endian::<BigEndian>().with_len::<U16>();
+    let mut unknown = [0u8; 1024];
+    let mut expected = [0u8; 1024];
+    let len = serialize_with(&mut unknown, &Evens(600), opts).unwrap();
+    assert_eq!(
+        serialize_with(&mut expected, &known(600)[..], opts).unwrap(),
+        len
+    );
+    assert_eq!(unknown[..2], [1, 44]);
+    assert_eq!(unknown[..len], expected[..len]);
+}
+
+#[test]
+fn map() {
+    let mut buf = [0u8; 32];
+    let len = encode(&mut buf, &Halves(5));
+    assert_eq!(len, 14);
+    assert_eq!(buf[..len], [3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 4, 2]);
+}
+
+#[test]
+fn too_many() {
+    let opts = Config::new().with_len::<U8>();
+    let mut buf = [0u8; 1024];
+    let err = serialize_with(&mut buf, &(1u8, Evens(600)), opts).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::Overflow);
+    assert_eq!(err.offset(), Some(1));
+}
+
+#[test]
+fn uninit() {
+    let mut buf = [MaybeUninit::uninit(); 32];
+    let mut ser =
+        ssmarshal::Serializer::from_output(UninitOutput::new(&mut buf), DefaultOptions::new());
+    ser.serialize(&Evens(4)).unwrap();
+    assert_eq!(
+        ser.into_output().into_init(),
+        &[2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0]
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn vec() {
+    assert_eq!(
+        ssmarshal::to_vec(&Evens(9)).unwrap(),
+        ssmarshal::to_vec(&known(9)).unwrap()
+    );
+}
+
+#[cfg(not(feature = "debug-panics"))]
+#[test]
+fn cannot_patch() {
+    use std::collections::hash_map::DefaultHasher;
+
+    use ssmarshal::output::HashOutput;
+
+    let out = HashOutput::new(DefaultHasher::new());
+    let mut ser = ssmarshal::Serializer::from_output(out, DefaultOptions::new());
+    let err = ser.serialize(&(1u8, Evens(4))).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::NotSupported);
+    assert_eq!(err.offset(), Some(1));
+}
+
+#[cfg(not(feature = "debug-panics"))]
+#[test]
+fn wrong_length() {
+    use serde::ser::SerializeSeq;
+
+    /// A sequence which claims one more element than it has.
+    struct Miscounted;
+
+    impl Serialize for Miscounted {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(Some(3))?;
+            seq.serialize_element(&1u8)?;
+            seq.serialize_element(&2u8)?;
+            seq.end()
+        }
+    }
+
+    let mut buf = [0u8; 32];
+    let err = serialize_with(&mut buf, &(1u8, Miscounted), DefaultOptions::new()).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::LengthMismatch { len: 3, count: 2 });
+    assert_eq!(err.offset(), Some(1));
+}
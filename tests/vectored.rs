@@ -0,0 +1,151 @@
+// Copyright (c) 2017 The Robigalia Project Developers Licensed under the Apache License, Version
+// 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. All files in the project
+// carrying such notice may not be copied, modified, or distributed except according to those
+// terms.
+
+extern crate quickcheck;
+extern crate quickcheck_macros;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate ssmarshal;
+
+mod common;
+
+use std::mem::size_of;
+
+use ssmarshal::output::{SegmentedOutput, WordOutput};
+use ssmarshal::{
+    deserialize_vectored, deserialize_words, serialize, serialize_vectored, serialize_words,
+    DefaultOptions, Serializer,
+};
+
+use common::{encoded, message, Message};
+
+#[test]
+fn every_split() {
+    let expected = encoded(&message());
+    for at in 0..=expected.len() {
+        let mut head = vec![0u8; at];
+        let mut tail = [0u8; 64];
+        let len = serialize_vectored(&mut [&mut head[..], &mut [][..], &mut tail[..]], &message())
+            .unwrap();
+        assert_eq!(len, expected.len());
+        assert_eq!(head[..], expected[..at]);
+        assert_eq!(tail[..len - at], expected[at..]);
+
+        let segments = [&head[..], &[][..], &tail[..len - at]];
+        assert_eq!(
+            deserialize_vectored::<Message>(&segments).unwrap(),
+            (message(), len)
+        );
+    }
+}
+
+#[test]
+fn wrapped_ring() {
+    let expected = encoded(&message());
+    let mut ring = [0u8; 32];
+    let start = ring.len() - 5;
+    for (i, &b) in expected.iter().enumerate() {
+        ring[(start + i) % ring.len()] = b;
+    }
+    let (back, front) = ring.split_at(start);
+    let front_len = expected.len() - front.len();
+    assert_eq!(
+        deserialize_vectored::<Message>(&[front, &back[..front_len]]).unwrap(),
+        (message(), expected.len())
+    );
+}
+
+#[cfg(not(feature = "debug-panics"))]
+#[test]
+fn too_short() {
+    use ssmarshal::ErrorKind;
+
+    let len = encoded(&message()).len();
+    let mut a = [0u8; 4];
+    let mut b = vec![0u8; len - 5];
+    let err = serialize_vectored(&mut [&mut a[..], &mut b[..]], &message()).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::EndOfStream);
+
+    let buf = encoded(&message());
+    let err = deserialize_vectored::<Message>(&[&buf[..4], &buf[4..len - 1]]).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::EndOfStream);
+}
+
+/// A sequence of unknown length, whose length gets patched in across a segment boundary.
+struct Streamed;
+
+impl serde::Serialize for Streamed {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq((1..=3u8).filter(|_| true))
+    }
+}
+
+#[test]
+fn patch_across_segments() {
+    let mut a = [0u8; 3];
+    let mut b = [0u8; 2];
+    let mut c = [0u8; 16];
+    let mut segments = [&mut a[..], &mut b[..], &mut c[..]];
+    let out = SegmentedOutput::new(&mut segments);
+    let len = Serializer::from_output(out, DefaultOptions::new())
+        .serialize(&(9u8, Streamed))
+        .unwrap();
+    assert_eq!(len, 12);
+    assert_eq!(a, [9, 3, 0]);
+    assert_eq!(b, [0, 0]);
+    assert_eq!(c[..7], [0, 0, 0, 0, 1, 2, 3]);
+}
+
+fn word_bytes(words: &[usize]) -> Vec<u8> {
+    words.iter().flat_map(|w| w.to_ne_bytes()).collect()
+}
+
+#[test]
+fn words() {
+    let expected = encoded(&message());
+    let mut words = [!0usize; 8];
+    let len = serialize_words(&mut words, &message()).unwrap();
+    assert_eq!(len, expected.len());
+    assert_eq!(word_bytes(&words)[..len], expected[..]);
+    // the rest of the last word is untouched
+    assert!(word_bytes(&words)[len..].iter().all(|&b| b == 0xFF));
+
+    let used = len.div_ceil(size_of::<usize>());
+    assert_eq!(
+        deserialize_words::<Message>(&words[..used]).unwrap(),
+        (message(), len)
+    );
+}
+
+#[test]
+fn words_used() {
+    let mut words = [0usize; 4];
+    let mut ser = Serializer::from_output(WordOutput::new(&mut words), DefaultOptions::new());
+    ser.serialize(&1u8).unwrap();
+    assert_eq!(ser.into_output().words_used(), 1);
+
+    let mut ser = Serializer::from_output(WordOutput::new(&mut words), DefaultOptions::new());
+    ser.serialize(&(Streamed, 0u16)).unwrap();
+    assert_eq!(
+        ser.into_output().words_used(),
+        13usize.div_ceil(size_of::<usize>())
+    );
+    assert_eq!(word_bytes(&words)[..8], 3u64.to_le_bytes());
+}
+
+#[quickcheck_macros::quickcheck]
+fn arbitrary_splits(val: (u16, Option<u64>, char, (i32, i32, i32)), cuts: Vec<u8>) -> bool {
+    let mut buf = [0u8; 64];
+    let len = serialize(&mut buf, &val).unwrap();
+    let mut cuts: Vec<usize> = cuts.into_iter().map(|c| c as usize % (len + 1)).collect();
+    cuts.push(0);
+    cuts.push(len);
+    cuts.sort_unstable();
+    let segments: Vec<&[u8]> = cuts.windows(2).map(|w| &buf[w[0]..w[1]]).collect();
+    deserialize_vectored(&segments).unwrap() == (val, len)
+}